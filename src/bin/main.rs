@@ -1,6 +1,22 @@
 use clap::{App, Arg, SubCommand};
-use std::path::PathBuf;
 use libsvctrl::*;
+use std::collections::VecDeque;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Format of the output of status, show and config
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    /// The same output as sv
+    Text,
+    /// JSON on a single line
+    Json,
+    /// Tab separated values
+    Tsv,
+}
 
 macro_rules! exit {
     () => {
@@ -19,11 +35,40 @@ fn main() {
         .arg(
             Arg::with_name("config")
                 .help("Path to config file to used")
+                .long_help("Path to a configuration file loaded on top of the ones in /usr/share, /etc and /run and of the SVCTRL_* environment variables, so only the values it sets are changed. If it isn't given the file in $SVCTRL_CONFIG is used, if any. See config --explain.")
                 .global(true)
                 .short("c")
                 .long("config")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("wait")
+                .help("Seconds to wait for services to reach the requested state")
+                .long_help("After sending the control command, wait up to SECS seconds, which can have a fraction like 0.5 and be at most a day, for each service to reach the state the command asks for, and exit with an error if any of them doesn't. disable waits this long instead of stop_timeout. Commands that only send a signal to the service, like hup or usr1, don't wait.")
+                .global(true)
+                .short("w")
+                .long("wait")
+                .value_name("SECS")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("user")
+                .help("Manage the services of the user running svctrl")
+                .long_help("Manage the services run by the runsvdir of the user instead of the ones of the system. The configuration is read from svctrl/config.toml in $XDG_CONFIG_HOME, or ~/.config if it isn't set, and the services are in ~/.local/share/sv and enabled in ~/.local/service unless the configuration says otherwise. See user-setup to run the runsvdir of a user.")
+                .global(true)
+                .long("user"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .help("Format of the output of status, show and config")
+                .long_help("Print the output of status, show and config as text, JSON or tab separated values. In JSON status is an array with an object for each service holding the keys name, state, pid, since, seconds, paused, want_up, term and normallyup, and the key log holding an object with the same keys for the logging subservice or null. show is an array of names and config is an object with a key for each value of the configuration. In TSV status prints a header and then a line for each service and logging subservice, named service/log, with the same columns as the keys in JSON. show prints a name per line and config a key and its value per line.")
+                .global(true)
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&["text", "json", "tsv"])
+                .default_value("text")
+                .takes_value(true),
+        )
         // Reminder to add conflict with future disabled service
         .subcommand(
             SubCommand::with_name("show")
@@ -33,9 +78,194 @@ fn main() {
                         .help("shows only enabled services")
                         .short("e")
                         .long("enabled"),
+                )
+                .arg(
+                    Arg::with_name("down")
+                        .help("shows only enabled services that are normally down")
+                        .long_help("Shows only the enabled services that have a down file in their service directory, which runsv doesn't start when it starts. See set-default-down.")
+                        .short("d")
+                        .long("down"),
+                )
+                .arg(
+                    Arg::with_name("runlevel")
+                        .help("show the services enabled in a runlevel")
+                        .long_help("Show the services enabled in the directory of the runlevel in runsvdir instead of the ones in lndir, implies --enabled.")
+                        .long("runlevel")
+                        .value_name("RUNLEVEL")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("prints values of config")
+                .arg(
+                    Arg::with_name("explain")
+                        .help("show where each value was taken from")
+                        .long_help("Show for each value of the configuration where it was taken from. Each layer overrides the ones before it: the default, /usr/share/svctrl/config.toml, /etc/svctrl/config.toml, /run/svctrl/config.toml, the SVCTRL_* environment variables like SVCTRL_SVDIR or SVCTRL_STOP_TIMEOUT, the file given with --config or in $SVCTRL_CONFIG and the flags on the command line.")
+                        .long("explain"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("report broken entries in the active services directory")
+                .long_about("Report the entries of lndir that svctrl can't manage: dangling symlinks, like after a package removed a service from svdir, symlinks to directories outside of svdir, and files or directories that aren't symlinks. Exits with 1 if any is found.")
+        )
+        .subcommand(
+            SubCommand::with_name("clean")
+                .about("remove broken entries from the active services directory")
+                .long_about("Remove the dangling symlinks from lndir, see doctor. Files and directories that aren't symlinks are only reported, as they may be services that aren't managed by svctrl.")
+                .arg(
+                    Arg::with_name("foreign")
+                        .help("also remove symlinks outside of svdir")
+                        .long("foreign"),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .help("only print what would be removed")
+                        .short("n")
+                        .long("dry-run"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check-dir")
+                .about("check service directories for mistakes")
+                .long_about("Check that run, finish, check and log/run of each service are executable files runsv can execute, that is with a shebang or compiled, and that run and log/run replace the shell with exec instead of leaving the service in the background. log/run is required if the service has a log directory. Exits with 1 if an error is found, warnings only get printed.")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to check")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("runlevel")
+                .about("manage runlevels")
+                .long_about("Manage the runlevels in runsvdir, each a directory of enabled services. The runlevel in use is the one the current symlink in runsvdir points at, which lndir usually points at too.")
+                .subcommand(SubCommand::with_name("list").about("list the runlevels"))
+                .subcommand(SubCommand::with_name("current").about("print the runlevel in use"))
+                .subcommand(
+                    SubCommand::with_name("switch")
+                        .about("switch to a runlevel")
+                        .arg(
+                            Arg::with_name("name")
+                                .help("runlevel to switch to")
+                                .long_help("Point the current symlink in runsvdir at the runlevel like runsvchdir does, keeping the runlevel in use as previous. runsvdir then stops the services that aren't enabled in the runlevel and starts the ones that are.")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .about("create an empty runlevel")
+                        .arg(
+                            Arg::with_name("name")
+                                .help("runlevel to create")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("user-setup")
+                .about("create a service running the runsvdir of a user")
+                .arg(
+                    Arg::with_name("username")
+                        .help("user to run the services of")
+                        .long_help("Create the service runsvdir-USER in the service directory of the system, which runs runsvdir as the user on ~/.local/service so the user can manage their services with --user. The service is not enabled and an existing service is never overwritten.")
+                        .value_name("USER")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("new")
+                .about("create a service directory")
+                .long_about("Create a service directory in svdir with run, finish and env/, and log/run if asked to. Each script is taken from the file of the same path in templatedir, or else from the built in template, with @NAME@, @EXEC@, @CHPST@ and @LOGDIR@ replaced. The service is not enabled and an existing service is never overwritten.")
+                .arg(
+                    Arg::with_name("name")
+                        .help("name of the service")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("exec")
+                        .help("command run replaces itself with")
+                        .long_help("Command that run replaces itself with, it must stay in the foreground for runsv to supervise it. It is used verbatim by sh.")
+                        .long("exec")
+                        .value_name("COMMAND")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("run-as")
+                        .help("user to run the command as")
+                        .long_help("User that run runs the command as with chpst -u. Named --run-as as --user selects the services of the user running svctrl.")
+                        .short("u")
+                        .long("run-as")
+                        .value_name("USER")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("log")
+                        .help("create log/run logging with svlogd")
+                        .long_help("Create log/run, which logs the output of run with svlogd to the directory named after the service in logdir of the configuration, or else in /var/log.")
+                        .long("log"),
+                )
+                .arg(
+                    Arg::with_name("env")
+                        .help("variable to set in env/")
+                        .long_help("Variable to write to env/, which run sets with chpst -e. Can be given more than once.")
+                        .long("env")
+                        .value_name("KEY=VALUE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("env")
+                .about("manage the environment of a service")
+                .long_about("Manage the variables in env/ of the service directory, which run sets with chpst -e. Each variable is a file holding its value in the first line, with newlines written as NUL. An empty file removes the variable from the environment the service inherits.")
+                .arg(
+                    Arg::with_name("service")
+                        .help("service to manage the environment of")
+                        .required(true),
+                )
+                .subcommand(SubCommand::with_name("list").about("print the variables"))
+                .subcommand(
+                    SubCommand::with_name("get")
+                        .about("print the value of a variable")
+                        .arg(Arg::with_name("key").value_name("KEY").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("set variables")
+                        .long_about("Set variables, each file is replaced atomically. Values can't end with spaces, tabs or newlines as chpst removes them.")
+                        .arg(
+                            Arg::with_name("pairs")
+                                .value_name("KEY=VALUE")
+                                .multiple(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("restart")
+                                .help("restart the service if it is running and a variable changed")
+                                .long("restart"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("unset")
+                        .about("remove variables")
+                        .long_about("Remove the files of variables, so the service inherits them from runsv.")
+                        .arg(
+                            Arg::with_name("keys")
+                                .value_name("KEY")
+                                .multiple(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("restart")
+                                .help("restart the service if it is running and a variable was removed")
+                                .long("restart"),
+                        ),
                 ),
         )
-        .subcommand(SubCommand::with_name("config").about("prints values of config"))
         .subcommand(
             SubCommand::with_name("enable")
                 .about("Enable a service")
@@ -45,6 +275,26 @@ fn main() {
                         .long_help("If the service is not present in the active directory try to symlink it to the active directory.")
                         .multiple(true)
                         .required(true),
+                )
+                .arg(
+                    Arg::with_name("no-deps")
+                        .help("ignore dependencies between services")
+                        .long_help("Only enable the services given. By default every service they depend on or want, declared in the svctrl.toml of their service directory, is enabled before them.")
+                        .long("no-deps"),
+                )
+                .arg(
+                    Arg::with_name("no-check")
+                        .help("enable services even if check-dir finds errors")
+                        .long_help("Enable the services without refusing the ones whose service directory has errors, see check-dir. Warnings are printed either way.")
+                        .long("no-check"),
+                )
+                .arg(
+                    Arg::with_name("runlevel")
+                        .help("enable in a runlevel")
+                        .long_help("Enable the services in the directory of the runlevel in runsvdir instead of in lndir. They are started when the system switches to the runlevel if it isn't the current one.")
+                        .long("runlevel")
+                        .value_name("RUNLEVEL")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -56,16 +306,50 @@ fn main() {
                         .long_help("If the service is enabled, try to shut it down by sending the down subcommand to it and then removing the symlink that keeps it active in the eyes of runsv. A service can only be disabled if it is enabled by a system of symlink to a directory that runsv supervises, to avoid deleting a service definition by accident.")
                         .multiple(true)
                         .required(true),
+                )
+                .arg(
+                    Arg::with_name("no-deps")
+                        .help("ignore dependencies between services")
+                        .long_help("Only disable the services given. By default every enabled service that depends on them, declared in the svctrl.toml of its service directory, is disabled before them.")
+                        .long("no-deps"),
+                )
+                .arg(
+                    Arg::with_name("escalate")
+                        .help("send TERM and then KILL to services that don't stop")
+                        .long_help("If the service doesn't go down within stop_timeout seconds, or the value of --wait, send it a TERM signal, wait as long again, and then send it a KILL signal and wait as long again before giving up. Overrides stop_escalate in the configuration.")
+                        .long("escalate"),
+                )
+                .arg(
+                    Arg::with_name("runlevel")
+                        .help("disable in a runlevel")
+                        .long_help("Disable the services in the directory of the runlevel in runsvdir instead of in lndir. If the runlevel isn't the current one the services are only removed from it and not stopped.")
+                        .long("runlevel")
+                        .value_name("RUNLEVEL")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("up").about("up a service").arg(
-                Arg::with_name("services")
-                    .help("services to bring up")
-                    .long_help("If the service is not running, start it. If the service stops, restart it.")
-                    .multiple(true)
-                    .required(true),
-            ),
+            SubCommand::with_name("up")
+                .about("up a service")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to bring up")
+                        .long_help("If the service is not running, start it. If the service stops, restart it.")
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("no-deps")
+                        .help("ignore dependencies between services")
+                        .long_help("Only bring up the services given. By default every service they depend on or want, declared in the svctrl.toml of their service directory, is brought up before them, waiting for what they depend on to be up.")
+                        .long("no-deps"),
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .help("wait for ./check to succeed")
+                        .long_help("After the service is up, run its ./check script until it succeeds, waiting up to 7 seconds, or the value of --wait, for the service to be up and ready.")
+                        .long("check"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("down")
@@ -76,6 +360,55 @@ fn main() {
                         .long_help("If the service is running, send it a TERM signal, and then a CONT signal. If ./run exits, start ./finish if it exists. After it stops, do not restart service.")
                         .multiple(true)
                         .required(true),
+                )
+                .arg(
+                    Arg::with_name("no-deps")
+                        .help("ignore dependencies between services")
+                        .long_help("Only bring down the services given. By default every enabled service that depends on them, declared in the svctrl.toml of its service directory, is brought down before them, waiting for it to be down.")
+                        .long("no-deps"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("mask")
+                .about("keep a service from being enabled or brought up")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to mask")
+                        .long_help("Create a file named after the service in maskdir. enable, up and once refuse to act on a masked service until it is unmasked. The service doesn't need to exist and is neither stopped nor disabled.")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unmask")
+                .about("allow a masked service to be enabled again")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to unmask")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-default-down")
+                .about("don't start a service when runsv starts")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to make normally down")
+                        .long_help("Create a down file in the service directory so runsv doesn't start the service when it starts, like at boot. A running service is not stopped.")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-default-up")
+                .about("start a service when runsv starts")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to make normally up")
+                        .long_help("Remove the down file from the service directory so runsv starts the service when it starts, like at boot. A stopped service is not started.")
+                        .multiple(true)
+                        .required(true),
                 ),
         )
         .subcommand(
@@ -190,6 +523,102 @@ fn main() {
                         .required(true),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("restart")
+                .about("restart a service and wait for it")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to restart")
+                        .long_help("Send the service the TERM, CONT and up commands, and wait up to 7 seconds, or the value of --wait, for the service to restart. Then report the status or timeout.")
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .help("wait for ./check to succeed")
+                        .long_help("After the service is restarted, run its ./check script until it succeeds within the same time the restart is waited for.")
+                        .long("check"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("check a service is in its requested state")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to check")
+                        .long_help("Wait up to 7 seconds, or the value of --wait, for the service to reach the state that was requested. If the service should be up and has a ./check script, run it until it exits with 0. Then report the status or failure.")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("try-restart")
+                .about("restart a service if it is running")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to restart")
+                        .long_help("If the service is running, send it the TERM and CONT commands, and wait up to 7 seconds, or the value of --wait, for the service to restart. Then report the status or timeout.")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("reload")
+                .about("send HUP signal and check the service")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to reload")
+                        .long_help("If the service is running, send it a HUP signal, give it half a second to handle it, and then report the status. Fail if the service is not running before the signal, or no longer runs with the same pid after it.")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("force-stop")
+                .about("down a service and KILL it on timeout")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to stop")
+                        .long_help("Same as down, but wait up to 7 seconds, or the value of --wait, for the service to become down. On timeout send the service a TERM signal and wait as long again, then a KILL signal and wait as long again, so a service that never goes down takes three times the wait. Then report the status.")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("force-reload")
+                .about("restart a service and KILL it on timeout")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to reload")
+                        .long_help("Send the service the TERM and CONT commands, and wait up to 7 seconds, or the value of --wait, for the service to restart. Then report the status, and on timeout send the service a KILL signal.")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("log")
+                .about("show the log of a service")
+                .arg(
+                    Arg::with_name("service")
+                        .help("service to show the log of")
+                        .long_help("Print the log that svlogd writes for the service, the rotated files first and then current. The log directory is taken from the svlogd command in log/run, or else is the directory named after the service in logdir of the configuration. TAI64N timestamps written by svlogd -t are shown as UTC time.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("lines")
+                        .help("number of lines to show")
+                        .short("n")
+                        .long("lines")
+                        .value_name("N")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("follow")
+                        .help("keep printing lines as they are logged")
+                        .short("f")
+                        .long("follow"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("status")
                 .about("get status of a service")
@@ -208,11 +637,139 @@ fn main() {
                         .short("a")
                         .long("all")
                         .conflicts_with("services"),
+                )
+                .arg(
+                    Arg::with_name("resources")
+                        .help("show the processes of each service and what they use")
+                        .long_help("Show the main process of each service and its descendants, found through the parent pid in /proc, with their resident memory, CPU time, threads, open file descriptors and command line instead of the status.")
+                        .short("r")
+                        .long("resources"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("top")
+                .about("show what the processes of each service use")
+                .long_about("Show for each service the resident memory, CPU time, threads and open file descriptors of its main process and its descendants together, found through the parent pid in /proc, largest first. The logging subservice is shown as SERVICE/log. See status --resources for each process.")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to show, all the enabled ones if none are given")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .help("what to sort the services by")
+                        .short("s")
+                        .long("sort")
+                        .possible_values(&["rss", "cpu", "threads", "fds"])
+                        .default_value("rss")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ports")
+                .about("show the sockets each service listens on")
+                .long_about("Show the TCP and UDP ports and unix sockets that the main process of each service and its descendants listen on, found by matching the sockets in /proc/net/{tcp,tcp6,udp,udp6,unix} with the open file descriptors in /proc. The logging subservice is shown as SERVICE/log. Only root can see the sockets of services that run as other users.")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to show, all the enabled ones if none are given")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("metrics")
+                .about("print the state of the enabled services as Prometheus metrics")
+                .long_about("Print gauges of each enabled service in the text format of Prometheus: whether runsv supervises it, whether it is up, its pid, the seconds since it changed state, whether it is normally up, wanted up or paused, and whether its logging subservice is up. With --output they are written to a file for the textfile collector of node_exporter, which is replaced atomically. Exits with 1 without touching it if the active services directory can't be read.")
+                .arg(
+                    Arg::with_name("output")
+                        .help("file to write the metrics to instead of stdout, like svctrl.prom")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("flapping")
+                .about("list services that keep restarting")
+                .long_about("List the enabled services that runsv restarted at least flap_restarts times within the last flap_window seconds. Restarts are recorded in flapping.json in statedir each time flapping looks at a service, at the time runsv wrote in supervise/status, so running it every now and then, or with --sample, builds up the count. Exits with 1 if a service is flapping.")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to look at, all the enabled ones if none are given")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .help("list every service with its restart count")
+                        .short("a")
+                        .long("all"),
+                )
+                .arg(
+                    Arg::with_name("sample")
+                        .help("seconds to keep looking at the services")
+                        .long_help("Seconds to keep looking at the services, reading their status whenever runsv writes it, so each restart in that time is seen instead of only the last one.")
+                        .long("sample")
+                        .value_name("SECONDS")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("restarts")
+                        .help("restarts after which a service is flapping, overrides flap_restarts")
+                        .long("restarts")
+                        .value_name("N")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("window")
+                        .help("seconds in which restarts are counted, overrides flap_window")
+                        .long("window")
+                        .value_name("SECONDS")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("down")
+                        .help("bring flapping services down, like setting flap_down")
+                        .long_help("Send the down command to flapping services that runsv wants up, so it stops restarting them until they are brought up again.")
+                        .long("down"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("show the status of services as it changes")
+                .long_about("Show the status of services and refresh it whenever runsv writes supervise/status, or every interval. State changes, new pids and restarts are listed as they happen, and services that restarted flap_restarts times within flap_window seconds are highlighted, and brought down if flap_down is set, see flapping. With --format json or tsv each change is printed as a line instead. Stops on Ctrl-C.")
+                .arg(
+                    Arg::with_name("services")
+                        .help("services to watch, all the enabled ones if none are given")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .help("seconds between refreshes")
+                        .long_help("Seconds between refreshes, which also happen whenever runsv writes the status of a service unless --no-inotify is given.")
+                        .short("n")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .default_value("2")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("no-inotify")
+                        .help("only refresh every interval")
+                        .long("no-inotify"),
                 ),
         )
         .get_matches();
 
-    let mut conf = configuration::Config::new();
+    let mut conf = if matches.is_present("user") {
+        match configuration::Config::user() {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+                exit!(fail => 1);
+            }
+        }
+    } else {
+        configuration::Config::new()
+    };
 
     // Try getting config from flags, set it to None if none is given
     let conf_path: Option<PathBuf> = match matches.value_of("config") {
@@ -228,33 +785,103 @@ fn main() {
         }
     }
 
-    if matches.is_present("show") {
-        if let Some(ref matches) = matches.subcommand_matches("show") {
-            if matches.is_present("enabled") {
-                if let Some(e) = servicedir::show_active_services(&conf) {
-                    for x in &e {
-                        println!("{}", x);
-                    }
-                };
-            };
-        };
-        if let Some(e) = servicedir::show_all_services(&conf) {
-            for x in &e {
-                println!("{}", x);
+    // Try getting the time to wait for services, set it to None if none is given
+    let wait: Option<Duration> = parse_seconds(&matches, "wait");
+
+    let format: Format = match matches.value_of("format") {
+        Some("json") => Format::Json,
+        Some("tsv") => Format::Tsv,
+        _ => Format::Text,
+    };
+
+    // Values given on the command line override the ones in the configuration
+    let mut cli = configuration::Layer::default();
+
+    if let Some(sub_m) = matches.subcommand_matches("disable") {
+        if sub_m.is_present("escalate") {
+            cli.stop_escalate = Some(true);
+        }
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("flapping") {
+        cli.flap_restarts = parse_arg(sub_m, "restarts");
+        cli.flap_window = parse_arg(sub_m, "window");
+        if sub_m.is_present("down") {
+            cli.flap_down = Some(true);
+        }
+    }
+
+    // A runlevel given to enable, disable or show replaces lndir
+    let mut level: Option<&str> = None;
+
+    if let (_, Some(sub_m)) = matches.subcommand() {
+        if let Some(l) = sub_m.value_of("runlevel") {
+            match runlevel::path(&conf, l) {
+                Ok(p) => cli.lndir = Some(p),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    exit!(fail => 1);
+                }
             }
+            level = Some(l);
+        }
+    }
+
+    conf.apply(cli, configuration::Source::Cli);
+
+    if let Some(ref sub_m) = matches.subcommand_matches("show") {
+        let services = if sub_m.is_present("down") {
+            servicedir::show_normally_down(&conf)
+        } else if sub_m.is_present("enabled") || level.is_some() {
+            servicedir::show_active_services(&conf)
+        } else {
+            servicedir::show_all_services(&conf)
         };
+
+        print_names(&services.unwrap_or_default(), format);
         exit!();
     }
 
-    if matches.is_present("config") {
-        println!("{}", conf);
+    // -c is also called config, so look at the subcommand
+    if let Some(sub_m) = matches.subcommand_matches("config") {
+        print_config(&conf, format, sub_m.is_present("explain"));
         exit!();
     }
 
-    let mut sv: service::Service = service::Service::new(conf.clone());
+    if matches.subcommand_name() == Some("doctor") {
+        doctor(&conf, format);
+        exit!();
+    }
 
-    match sv.get_paths() {
-        Ok(_) => (),
+    if let Some(sub_m) = matches.subcommand_matches("clean") {
+        clean(&conf, sub_m);
+        exit!();
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("check-dir") {
+        check_dirs(&conf, sub_m, format);
+        exit!();
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("new") {
+        new_service(&conf, sub_m);
+        exit!();
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("runlevel") {
+        manage_runlevels(&conf, sub_m, format);
+        exit!();
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("user-setup") {
+        user_setup(&conf, sub_m);
+        exit!();
+    }
+
+    let mut sv: service::Service = service::Service::new(conf.clone());
+
+    match sv.get_paths() {
+        Ok(_) => (),
         Err(e) => {
             eprintln!("ERROR: {}", e);
             exit!(fail => 1);
@@ -264,30 +891,79 @@ fn main() {
     match matches.subcommand_name() {
         // Those that exit directly are ones that are already
         // handlded
-        Some("enable") => enable_services(sv, matches),
-        Some("disable") => disable_services(sv, matches),
-        Some("up") => send_signals(sv, "up", "u", matches),
-        Some("down") => send_signals(sv, "down", "d", matches),
-        Some("once") => send_signals(sv, "once", "o", matches),
-        Some("stop") => send_signals(sv, "stop", "p", matches),
-        Some("cont") => send_signals(sv, "cont", "c", matches),
-        Some("hup") => send_signals(sv, "hup", "h", matches),
-        Some("alarm") => send_signals(sv, "alarm", "a", matches),
-        Some("int") => send_signals(sv, "int", "i", matches),
-        Some("quit") => send_signals(sv, "quit", "q", matches),
-        Some("usr1") => send_signals(sv, "usr1", "1", matches),
-        Some("usr2") => send_signals(sv, "usr2", "2", matches),
-        Some("term") => send_signals(sv, "term", "t", matches),
-        Some("kill") => send_signals(sv, "kill", "k", matches),
-        Some("exit") => send_signals(sv, "exit", "e", matches),
+        Some("enable") => enable_services(sv, &conf, matches),
+        Some("disable") => {
+            // Services of a runlevel not in use may be supervised by the one in use
+            let inactive = match level {
+                Some(l) => runlevel::current(&conf).ok().as_deref() != Some(l),
+                None => false,
+            };
+            disable_services(sv, &conf, matches, inactive, wait)
+        }
+        Some("mask") => mask_services(sv, "mask", matches),
+        Some("unmask") => mask_services(sv, "unmask", matches),
+        Some("set-default-down") => set_default(sv, "set-default-down", false, matches),
+        Some("set-default-up") => set_default(sv, "set-default-up", true, matches),
+        Some("up") => send_signals(sv, &conf, "up", "u", matches, wait),
+        Some("down") => send_signals(sv, &conf, "down", "d", matches, wait),
+        Some("once") => send_signals(sv, &conf, "once", "o", matches, wait),
+        Some("stop") => send_signals(sv, &conf, "stop", "p", matches, wait),
+        Some("cont") => send_signals(sv, &conf, "cont", "c", matches, wait),
+        Some("hup") => send_signals(sv, &conf, "hup", "h", matches, wait),
+        Some("alarm") => send_signals(sv, &conf, "alarm", "a", matches, wait),
+        Some("int") => send_signals(sv, &conf, "int", "i", matches, wait),
+        Some("quit") => send_signals(sv, &conf, "quit", "q", matches, wait),
+        Some("usr1") => send_signals(sv, &conf, "usr1", "1", matches, wait),
+        Some("usr2") => send_signals(sv, &conf, "usr2", "2", matches, wait),
+        Some("term") => send_signals(sv, &conf, "term", "t", matches, wait),
+        Some("kill") => send_signals(sv, &conf, "kill", "k", matches, wait),
+        Some("exit") => send_signals(sv, &conf, "exit", "e", matches, wait),
+        Some("restart") => lsb_action(sv, "restart", matches, wait),
+        Some("try-restart") => lsb_action(sv, "try-restart", matches, wait),
+        Some("reload") => lsb_action(sv, "reload", matches, wait),
+        Some("force-stop") => lsb_action(sv, "force-stop", matches, wait),
+        Some("force-reload") => lsb_action(sv, "force-reload", matches, wait),
+        Some("check") => lsb_action(sv, "check", matches, wait),
+        Some("log") => show_log(sv, &conf, matches),
+        Some("env") => manage_env(sv, matches, format, wait),
+        Some("watch") => watch_services(sv, &conf, matches, format),
+        Some("flapping") => flapping(sv, &conf, matches, format),
+        Some("top") => {
+            if let Some(sub_m) = matches.subcommand_matches("top") {
+                let names: Vec<String> = match sub_m.values_of("services") {
+                    Some(v) => v.map(|s| s.to_string()).collect(),
+                    None => servicedir::show_active_services(&conf).unwrap_or_default(),
+                };
+                print_resources(sv, &names, format, sub_m.value_of("sort"));
+            }
+        }
+        Some("metrics") => {
+            if let Some(sub_m) = matches.subcommand_matches("metrics") {
+                write_metrics(sv, &conf, sub_m.value_of("output"));
+            }
+        }
+        Some("ports") => {
+            if let Some(sub_m) = matches.subcommand_matches("ports") {
+                let names: Vec<String> = match sub_m.values_of("services") {
+                    Some(v) => v.map(|s| s.to_string()).collect(),
+                    None => servicedir::show_active_services(&conf).unwrap_or_default(),
+                };
+                print_ports(sv, &names, format);
+            }
+        }
         Some("status") => {
             if let Some(ref sub_m) = matches.subcommand_matches("status") {
-                if let Some(args) = sub_m.values_of("services") {
-                    get_status_of(sv, args);
-                } else if sub_m.is_present("all") {
-                    if let Some(dirs) = servicedir::show_active_services(&conf) {
-                        get_status_of(sv, dirs.iter());
+                if sub_m.is_present("resources") {
+                    let names: Vec<String> = match sub_m.values_of("services") {
+                        Some(v) => v.map(|s| s.to_string()).collect(),
+                        None => servicedir::show_active_services(&conf).unwrap_or_default(),
                     };
+                    print_resources(sv, &names, format, None);
+                } else if let Some(args) = sub_m.values_of("services") {
+                    get_status_of(sv, args, format);
+                } else if sub_m.is_present("all") {
+                    let dirs = servicedir::show_active_services(&conf).unwrap_or_default();
+                    get_status_of(sv, dirs.iter(), format);
                 };
             }
         }
@@ -306,67 +982,309 @@ fn main() {
 ///
 /// * `sv` - Service struct that will be modified to get status
 /// * `args` - Iterator over String that contains the names of the services to get the status of
-fn get_status_of<'a, I, S>(mut sv: service::Service, args: I)
+/// * `format` - Format to print the status in
+fn get_status_of<'a, I, S>(mut sv: service::Service, args: I, format: Format)
 where
     I: Iterator<Item = S>,
     S: AsRef<str>,
 {
+    let mut statuses: Vec<service::FullStatus> = Vec::new();
+
+    if format == Format::Tsv {
+        println!("name\tstate\tpid\tsince\tseconds\tpaused\twant_up\tterm\tnormallyup");
+    }
+
     for arg in args {
         sv = rename(sv, arg.as_ref());
 
-        // Start
-        let mut svs: service::Status = service::Status::default();
+        if format == Format::Text {
+            print_status(&sv, "");
+            continue;
+        }
 
-        match svs.status(&sv, false) {
-            Ok(s) => print!("{}", s),
+        let s = match service::FullStatus::new(&sv) {
+            Ok(s) => s,
             Err(e) => {
                 eprintln!(
                     "Failed to get status of service ({})! Error: {}",
                     &sv.name, e,
                 );
+                continue;
             }
         };
 
-        // Check if we have a log dir and and it
-        if sv.has_log() {
-            match svs.status(&sv, true) {
-                Ok(s) => println!("; {}", s),
-                Err(e) => {
-                    eprintln!(
-                        "Failed to get status of log service ({})! Error: {}",
-                        &sv.name, e,
-                    );
-                }
+        if let Some(e) = &s.log_error {
+            eprintln!(
+                "Failed to get status of log service ({})! Error: {}",
+                &sv.name, e,
+            );
+        }
+
+        if format == Format::Tsv {
+            print_tsv_status(&s.service, &sv.name);
+            if let Some(log) = &s.log {
+                print_tsv_status(log, &format!("{}/log", sv.name));
             }
         } else {
-            println!();
+            statuses.push(s);
+        }
+    }
+
+    if format == Format::Json {
+        print_json(&statuses);
+    }
+}
+
+/// Prints a line of the TSV output of status
+///
+/// # Arguments
+///
+/// * `s` - Status to print
+/// * `name` - String slice used as the name instead of the one in the status, so the
+///   logging subservice can be told apart from the service
+fn print_tsv_status(s: &service::Status, name: &str) {
+    let since = s.since().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    println!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        name,
+        s.state(),
+        s.pid(),
+        since.as_secs(),
+        s.seconds(),
+        s.paused(),
+        s.want_up(),
+        s.term(),
+        s.normallyup()
+    );
+}
+
+/// Prints a value as JSON on a single line, exits if it can't be serialized
+fn print_json<T: serde::Serialize>(v: &T) {
+    match serde_json::to_string(v) {
+        Ok(s) => println!("{}", s),
+        Err(e) => {
+            eprintln!("ERROR: failed to serialize output! Error: {}", e);
+            exit!(fail => 1);
+        }
+    }
+}
+
+/// Prints the names of services, one per line or as a JSON array
+fn print_names(names: &[String], format: Format) {
+    match format {
+        Format::Json => print_json(&names),
+        Format::Text | Format::Tsv => {
+            for x in names {
+                println!("{}", x);
+            }
+        }
+    }
+}
+
+/// Prints the configuration as TOML, as a JSON object or as a key and its value per line
+///
+/// # Arguments
+///
+/// * `conf` - Config to print
+/// * `format` - Format to print the configuration in
+/// * `explain` - Whether to also print where each value was taken from
+fn print_config(conf: &configuration::Config, format: Format, explain: bool) {
+    if format == Format::Text && !explain {
+        println!("{}", conf);
+        return;
+    }
+
+    let mut values = match serde_json::to_value(conf) {
+        Ok(serde_json::Value::Object(m)) => m,
+        Ok(_) => serde_json::Map::new(),
+        Err(e) => {
+            eprintln!("ERROR: failed to serialize output! Error: {}", e);
+            exit!(fail => 1);
+        }
+    };
+
+    if format == Format::Json && !explain {
+        print_json(&values);
+        return;
+    }
+
+    let mut explained = serde_json::Map::new();
+
+    for key in configuration::KEYS {
+        let value = values.remove(*key).unwrap_or_default();
+        let source = match conf.source(key) {
+            configuration::Source::Env => {
+                format!("environment (${})", configuration::env_var(key))
+            }
+            s => s.to_string(),
+        };
+
+        let text = match &value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+
+        match format {
+            Format::Text => match &value {
+                // TOML has no null, unset keys are left out of the file
+                serde_json::Value::Null => println!("# {} is not set # {}", key, source),
+                serde_json::Value::String(s) => println!("{} = '{}' # {}", key, s, source),
+                v => println!("{} = {} # {}", key, v, source),
+            },
+            Format::Tsv if explain => println!("{}\t{}\t{}", key, text, source),
+            Format::Tsv => println!("{}\t{}", key, text),
+            Format::Json => {
+                let mut entry = serde_json::Map::new();
+                entry.insert("value".to_string(), value);
+                entry.insert("source".to_string(), serde_json::Value::String(source));
+                explained.insert(key.to_string(), serde_json::Value::Object(entry));
+            }
+        }
+    }
+
+    if format == Format::Json {
+        print_json(&explained);
+    }
+}
+
+/// Prints the status of a service and its log subservice on a single line like sv does
+///
+/// # Arguments
+///
+/// * `sv` - Service struct to get the status of
+/// * `prefix` - String slice printed before the status, like 'ok: ' after a restart
+fn print_status(sv: &service::Service, prefix: &str) {
+    let mut svs: service::Status = service::Status::default();
+
+    match svs.status(sv, false) {
+        Ok(s) => print!("{}{}", prefix, s),
+        Err(e) => {
+            eprintln!(
+                "Failed to get status of service ({})! Error: {}",
+                &sv.name, e,
+            );
+        }
+    };
+
+    // Check if we have a log dir and and it
+    if sv.has_log() {
+        match svs.status(sv, true) {
+            Ok(s) => println!("; {}", s),
+            Err(e) => {
+                eprintln!(
+                    "Failed to get status of log service ({})! Error: {}",
+                    &sv.name, e,
+                );
+            }
         }
+    } else {
+        println!();
     }
 }
 
-/// Accepts any iterator of Str that represents the names of the services and a sends a signal
-/// to each of them
+/// Accepts an Order of the services and sends a signal to each of them, returns false if
+/// sending the signal or waiting for any of them failed
 ///
 /// # Arguments
 ///
 /// * `sv` - Service struct that will be modified to get status
-/// * `args` - Iterator over String that contains the names of the services to get the status of
+/// * `order` - Order holding the names of the services to signal and what each must wait for
 /// * `signal` - Slice string representing the signal that will be sent
-fn signal_each<'a, I, S>(mut sv: service::Service, args: I, signal: &str)
-where
-    I: Iterator<Item = S>,
-    S: AsRef<str>,
-{
-    for arg in args {
-        sv = rename(sv, arg.as_ref());
+/// * `wait` - Time to wait for all services to reach the state asked by the signal, if any
+/// * `check` - Whether to also wait for the ./check script of each service to succeed
+fn signal_each(
+    mut sv: service::Service,
+    order: &depends::Order,
+    signal: &str,
+    wait: Option<Duration>,
+    check: bool,
+) -> bool {
+    let mut ok: bool = true;
+    let mut signalled: Vec<String> = Vec::new();
+    let mut failed: Vec<String> = Vec::new();
+
+    // Conditions that involve a time are relative to right before we signal
+    let since = SystemTime::now();
+    let cond = service::Condition::from_signal(signal, since);
+
+    for name in &order.names {
+        // What this service depends on must reach the state before it is signalled
+        if let Some(c) = cond {
+            for dep in order.before(name) {
+                if !signalled.contains(dep) {
+                    continue;
+                }
+
+                sv = rename(sv, dep);
+
+                if let Err(e) = sv.wait_for(c, wait.unwrap_or(service::DEFAULT_WAIT)) {
+                    eprintln!("{}", e);
+                    signalled.retain(|n| n != dep);
+                    failed.push(dep.clone());
+                }
+            }
+        }
+
+        if let Some(dep) = order.failed_before(name, &failed) {
+            eprintln!("{}", Error::DepFailed(name.clone(), dep.clone()));
+            failed.push(name.clone());
+            ok = false;
+            continue;
+        }
+
+        sv = rename(sv, name);
 
         match sv.signal(signal) {
-            Ok(_) => continue,
+            Ok(_) => signalled.push(name.clone()),
             Err(e) => {
                 eprintln!("{}", e);
+                failed.push(name.clone());
+                ok = false;
+            }
+        }
+    }
+
+    // Checking for readiness always needs a deadline
+    let wait = if check {
+        Some(wait.unwrap_or(service::DEFAULT_WAIT))
+    } else {
+        wait
+    };
+
+    let (wait, cond) = match (wait, cond) {
+        (Some(w), Some(c)) => (w, c),
+        _ => return ok,
+    };
+
+    // All services share the same deadline, like sv does
+    let deadline = utils::deadline(wait);
+
+    for name in &signalled {
+        sv = rename(sv, name);
+
+        let mut result = sv.wait_for(cond, utils::time_left(deadline));
+
+        if result.is_ok() && check {
+            result = sv.wait_ready(utils::time_left(deadline));
+        }
+
+        if let Err(e) = result {
+            match e {
+                Error::Timeout(..) => eprintln!(
+                    "Timed out after {}s waiting for service ({}) to {}",
+                    wait.as_secs(),
+                    name,
+                    cond
+                ),
+                _ => eprintln!("{}", e),
             }
+            ok = false;
         }
     }
+
+    ok
 }
 
 /// Recieves a Service struct and renames it changing the name, srcpath and dstpath fields
@@ -385,44 +1303,1430 @@ fn rename(mut sv: service::Service, name: &str) -> service::Service {
     sv
 }
 
-fn send_signals(sv: service::Service, subcommand: &str, signal: &str, matches: clap::ArgMatches) {
-    if let Some(ref sub_m) = matches.subcommand_matches(subcommand) {
+/// Returns the Order to act on the services given to a subcommand in, following the
+/// dependencies between them unless --no-deps was given, exits if they can't be ordered
+///
+/// # Arguments
+///
+/// * `conf` - Config holding where to read the dependencies of each service from
+/// * `sub_m` - Matches of the subcommand holding the services
+/// * `stop` - Whether the services are being stopped instead of started
+fn order_of(conf: &configuration::Config, sub_m: &clap::ArgMatches, stop: bool) -> depends::Order {
+    let names: Vec<&str> = match sub_m.values_of("services") {
+        Some(v) => v.collect(),
+        None => Vec::new(),
+    };
+
+    if sub_m.is_present("no-deps") {
+        return depends::Order::plain(names.iter());
+    }
+
+    let order = if stop {
+        depends::stop_order(conf, &names)
+    } else {
+        depends::start_order(conf, &names)
+    };
+
+    match order {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit!(fail => 1);
+        }
+    }
+}
+
+fn send_signals(
+    sv: service::Service,
+    conf: &configuration::Config,
+    subcommand: &str,
+    signal: &str,
+    matches: clap::ArgMatches,
+    wait: Option<Duration>,
+) {
+    if let Some(sub_m) = matches.subcommand_matches(subcommand) {
+        // Only starting and stopping follow dependencies
+        let order = match signal {
+            "u" => order_of(conf, sub_m, false),
+            "d" => order_of(conf, sub_m, true),
+            _ => match sub_m.values_of("services") {
+                Some(args) => depends::Order::plain(args),
+                None => return,
+            },
+        };
+
+        if !signal_each(sv, &order, signal, wait, sub_m.is_present("check")) {
+            exit!(fail => 1);
+        }
+    }
+}
+
+/// Returns the exit code that LSB init scripts use for an error
+fn lsb_code(e: &Error) -> i32 {
+    match e {
+        // program is not configured
+        Error::NotEnabled(_) | Error::Disabled(_) | Error::Masked(_) => 6,
+        // program is not running
+        Error::NotRunning(_) => 7,
+        // generic or unspecified error, this includes timeouts
+        _ => 1,
+    }
+}
+
+/// Runs one of the compound actions of sv on each service given to a subcommand, reports
+/// the status of each service and exits with the highest LSB exit code of all services
+fn lsb_action(
+    mut sv: service::Service,
+    subcommand: &str,
+    matches: clap::ArgMatches,
+    wait: Option<Duration>,
+) {
+    let wait = wait.unwrap_or(service::DEFAULT_WAIT);
+    let mut code: i32 = 0;
+
+    if let Some(sub_m) = matches.subcommand_matches(subcommand) {
         if let Some(args) = sub_m.values_of("services") {
-            signal_each(sv, args, signal);
+            for arg in args {
+                sv = rename(sv, arg);
+
+                let deadline = utils::deadline(wait);
+
+                let mut killed = match subcommand {
+                    "restart" => sv.restart(wait).map(|_| false),
+                    "check" => sv.check(wait).map(|_| false),
+                    "try-restart" => sv.try_restart(wait).map(|_| false),
+                    "reload" => sv.reload().map(|_| false),
+                    "force-stop" => sv.force_stop(wait).map(|s| s == service::StopStep::Kill),
+                    "force-reload" => sv.force_reload(wait),
+                    _ => unreachable!(),
+                };
+
+                if killed.is_ok() && sub_m.is_present("check") {
+                    killed = sv.wait_ready(utils::time_left(deadline)).and(killed);
+                }
+
+                match killed {
+                    Ok(false) => print_status(&sv, "ok: "),
+                    Ok(true) => print_status(&sv, "kill: "),
+                    Err(Error::Timeout(..)) => {
+                        print_status(&sv, "timeout: ");
+                        code = code.max(1);
+                    }
+                    Err(Error::CheckFailed(_)) => {
+                        print_status(&sv, "fail: ");
+                        code = code.max(1);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        code = code.max(lsb_code(&e));
+                    }
+                }
+            }
         }
     }
+
+    if code != 0 {
+        exit!(fail => code);
+    }
 }
 
-fn disable_services(mut sv: service::Service, matches: clap::ArgMatches) {
-    if let Some(ref sub_m) = matches.subcommand_matches("disable") {
+/// Masks or unmasks the services given to a subcommand
+fn mask_services(mut sv: service::Service, subcommand: &str, matches: clap::ArgMatches) {
+    let mut ok = true;
+
+    if let Some(sub_m) = matches.subcommand_matches(subcommand) {
         if let Some(args) = sub_m.values_of("services") {
             for arg in args {
                 sv = rename(sv, arg);
 
-                match &sv.disable() {
-                    Ok(_) => println!("service '{}' disabled", arg),
+                let result = if subcommand == "mask" {
+                    sv.mask()
+                } else {
+                    sv.unmask()
+                };
+
+                match result {
+                    Ok(true) => println!("service '{}' {}ed", arg, subcommand),
+                    Ok(false) => println!("service '{}' is already {}ed", arg, subcommand),
                     Err(e) => {
                         eprintln!("{}", e);
+                        ok = false;
                     }
                 }
             }
         }
     }
+
+    if !ok {
+        exit!(fail => 1);
+    }
 }
 
-fn enable_services(mut sv: service::Service, matches: clap::ArgMatches) {
-    if let Some(ref sub_m) = matches.subcommand_matches("enable") {
+/// Makes the services given to a subcommand start or not start when runsv starts
+fn set_default(mut sv: service::Service, subcommand: &str, up: bool, matches: clap::ArgMatches) {
+    let mut ok = true;
+    let state = if up { "up" } else { "down" };
+
+    if let Some(sub_m) = matches.subcommand_matches(subcommand) {
         if let Some(args) = sub_m.values_of("services") {
             for arg in args {
                 sv = rename(sv, arg);
 
-                match &sv.enable() {
-                    Ok(_) => println!("service '{}' enabled", arg,),
+                match sv.set_normally_up(up) {
+                    Ok(true) => println!("service '{}' is now normally {}", arg, state),
+                    Ok(false) => println!("service '{}' is already normally {}", arg, state),
                     Err(e) => {
                         eprintln!("{}", e);
+                        ok = false;
                     }
                 }
             }
         }
     }
+
+    if !ok {
+        exit!(fail => 1);
+    }
+}
+
+/// Returns the entries of lndir with a problem, exits if lndir can't be read
+fn check_lndir(conf: &configuration::Config) -> Vec<servicedir::Entry> {
+    match servicedir::check_lndir(conf) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit!(fail => 1);
+        }
+    }
+}
+
+/// Describes an entry of lndir with a problem, like 'dangling symlink to /etc/sv/foo'
+fn describe(e: &servicedir::Entry) -> String {
+    match &e.target {
+        Some(t) => format!("{} to {:#?}", e.problem, t),
+        None => e.problem.to_string(),
+    }
+}
+
+/// Prints the entries of lndir with a problem and exits with 1 if there are any
+fn doctor(conf: &configuration::Config, format: Format) {
+    let found = check_lndir(conf);
+
+    match format {
+        Format::Text => {
+            for e in &found {
+                println!("{}: {}", e.name, describe(e));
+            }
+        }
+        Format::Json => print_json(&found),
+        Format::Tsv => {
+            println!("name\tproblem\ttarget");
+            for e in &found {
+                let target = match &e.target {
+                    Some(t) => t.display().to_string(),
+                    None => String::new(),
+                };
+                println!(
+                    "{}\t{}\t{}",
+                    e.name,
+                    format!("{:?}", e.problem).to_lowercase(),
+                    target
+                );
+            }
+        }
+    }
+
+    if !found.is_empty() {
+        exit!(fail => 1);
+    }
+}
+
+/// Prints the problems found in the service directories of services and exits with 1
+/// if any of them has errors
+fn check_dirs(conf: &configuration::Config, sub_m: &clap::ArgMatches, format: Format) {
+    let mut found: Vec<DirLint> = Vec::new();
+    let mut errors = false;
+
+    for name in sub_m.values_of("services").unwrap_or_default() {
+        let lints = lint::check_dir(&conf.svdir.join(name));
+
+        errors |= lint::has_errors(&lints);
+
+        found.extend(lints.into_iter().map(|lint| DirLint {
+            name: name.to_string(),
+            lint,
+        }));
+    }
+
+    match format {
+        Format::Text => {
+            for l in &found {
+                println!("{}: {}", l.name, l.lint);
+            }
+        }
+        Format::Json => print_json(&found),
+        Format::Tsv => {
+            println!("name\tfile\tseverity\tmessage");
+            for l in &found {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    l.name, l.lint.file, l.lint.severity, l.lint.message
+                );
+            }
+        }
+    }
+
+    if errors {
+        exit!(fail => 1);
+    }
+}
+
+/// Problem found by check-dir in the service directory of a service
+#[derive(serde::Serialize)]
+struct DirLint {
+    /// Name of the service
+    name: String,
+    #[serde(flatten)]
+    lint: lint::Lint,
+}
+
+/// Removes the dangling symlinks from lndir, and the foreign ones if asked to
+fn clean(conf: &configuration::Config, sub_m: &clap::ArgMatches) {
+    let dry_run = sub_m.is_present("dry-run");
+    let mut ok = true;
+
+    for e in check_lndir(conf) {
+        let remove = match e.problem {
+            servicedir::Problem::Dangling => true,
+            servicedir::Problem::Foreign => sub_m.is_present("foreign"),
+            servicedir::Problem::Plain => false,
+        };
+
+        if !remove {
+            println!("skipped '{}' ({})", e.name, describe(&e));
+            continue;
+        }
+
+        if dry_run {
+            println!("would remove '{}' ({})", e.name, describe(&e));
+            continue;
+        }
+
+        match servicedir::remove_entry(&e) {
+            Ok(_) => println!("removed '{}' ({})", e.name, describe(&e)),
+            Err(err) => {
+                eprintln!("{}", err);
+                ok = false;
+            }
+        }
+    }
+
+    if !ok {
+        exit!(fail => 1);
+    }
+}
+
+/// Runs the subcommand given to runlevel
+fn manage_runlevels(conf: &configuration::Config, sub_m: &clap::ArgMatches, format: Format) {
+    let result = match sub_m.subcommand() {
+        ("list", _) => runlevel::list(conf).map(|l| print_names(&l, format)),
+        ("current", _) => runlevel::current(conf).map(|c| println!("{}", c)),
+        ("switch", Some(m)) => {
+            let name = m.value_of("name").unwrap_or_default();
+            runlevel::switch(conf, name).map(|_| println!("switched to runlevel '{}'", name))
+        }
+        ("create", Some(m)) => {
+            let name = m.value_of("name").unwrap_or_default();
+            runlevel::create(conf, name).map(|_| println!("runlevel '{}' created", name))
+        }
+        _ => {
+            eprintln!("{}", sub_m.usage());
+            exit!(fail => 1);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        exit!(fail => 1);
+    }
+}
+
+/// Creates the service running the runsvdir of the user given to user-setup
+fn user_setup(conf: &configuration::Config, sub_m: &clap::ArgMatches) {
+    // The service goes in the service directory of the system
+    if conf.user {
+        eprintln!("ERROR: user-setup manages the services of the system, run it without --user");
+        exit!(fail => 1);
+    }
+
+    let username = sub_m.value_of("username").unwrap_or_default();
+
+    let result = user::home_of(username).and_then(|h| user::setup(conf, username, &h));
+
+    match result {
+        Ok(dir) => {
+            let name = format!("{}{}", user::SERVICE_PREFIX, username);
+            println!("service '{}' created in {:#?}", name, dir);
+            println!("enable it with: svctrl enable {}", name);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            exit!(fail => 1);
+        }
+    }
+}
+
+/// Creates a service directory from the templates
+fn new_service(conf: &configuration::Config, sub_m: &clap::ArgMatches) {
+    let mut env: Vec<(String, String)> = Vec::new();
+
+    for pair in sub_m.values_of("env").unwrap_or_default() {
+        match envdir::parse(pair) {
+            Ok(kv) => env.push(kv),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit!(fail => 1);
+            }
+        }
+    }
+
+    let new = template::NewService {
+        name: sub_m.value_of("name").unwrap_or_default().to_string(),
+        exec: sub_m.value_of("exec").unwrap_or_default().to_string(),
+        user: sub_m.value_of("run-as").map(|u| u.to_string()),
+        log: sub_m.is_present("log"),
+        env,
+    };
+
+    match new.create(conf) {
+        Ok(dir) => {
+            // Custom templates may have mistakes
+            for l in lint::check_dir(&dir) {
+                eprintln!("{}: {}", new.name, l);
+            }
+            println!("service '{}' created in {:#?}", new.name, dir);
+            println!("enable it with: svctrl enable {}", new.name);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            exit!(fail => 1);
+        }
+    }
+}
+
+/// Lists, gets, sets or unsets the variables in env/ of a service and restarts it if
+/// asked to and anything changed
+fn manage_env(
+    mut sv: service::Service,
+    matches: clap::ArgMatches,
+    format: Format,
+    wait: Option<Duration>,
+) {
+    let sub_m = match matches.subcommand_matches("env") {
+        Some(s) => s,
+        None => return,
+    };
+
+    sv = rename(sv, sub_m.value_of("service").unwrap_or_default());
+
+    let dir = match sv.envdir() {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit!(fail => 1);
+        }
+    };
+
+    let mut changed = false;
+    let mut ok = true;
+
+    match sub_m.subcommand() {
+        ("get", Some(m)) => {
+            let key = m.value_of("key").unwrap_or_default();
+
+            match envdir::get(&dir, key) {
+                Ok(Some(v)) => println!("{}", v),
+                Ok(None) => {
+                    eprintln!("{}", Error::NotSet(key.to_string()));
+                    ok = false;
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    ok = false;
+                }
+            }
+        }
+        ("set", Some(m)) => {
+            for pair in m.values_of("pairs").unwrap_or_default() {
+                let result = envdir::parse(pair).and_then(|(k, v)| envdir::set(&dir, &k, &v));
+
+                match result {
+                    Ok(c) => changed |= c,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        ok = false;
+                    }
+                }
+            }
+        }
+        ("unset", Some(m)) => {
+            for key in m.values_of("keys").unwrap_or_default() {
+                match envdir::unset(&dir, key) {
+                    Ok(c) => changed |= c,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        ok = false;
+                    }
+                }
+            }
+        }
+        // list is the default
+        _ => match envdir::list(&dir) {
+            Ok(vars) => print_env(&vars, format),
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+            }
+        },
+    }
+
+    let restart = match sub_m.subcommand() {
+        (_, Some(m)) => m.is_present("restart"),
+        _ => false,
+    };
+
+    // chpst only reads env/ when run starts
+    if restart && changed {
+        match sv.try_restart(wait.unwrap_or(service::DEFAULT_WAIT)) {
+            Ok(true) => print_status(&sv, "ok: "),
+            Ok(false) => (),
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+            }
+        }
+    }
+
+    if !ok {
+        exit!(fail => 1);
+    }
+}
+
+/// Escapes backslashes, tabs and newlines of the value of a variable so it fits on
+/// one line and a backslash followed by n in it isn't read as a newline
+fn escape_value(v: &str) -> String {
+    v.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+/// Prints the variables of an envdir, those removed by an empty file are printed as
+/// unset in text, null in JSON and false in the set column of TSV, values are
+/// escaped by `escape_value` in text and TSV
+fn print_env(vars: &[(String, Option<String>)], format: Format) {
+    match format {
+        Format::Text => {
+            for (k, v) in vars {
+                match v {
+                    Some(v) => println!("{}={}", k, escape_value(v)),
+                    None => println!("{} is unset", k),
+                }
+            }
+        }
+        Format::Json => {
+            let map: serde_json::Map<String, serde_json::Value> = vars
+                .iter()
+                .map(|(k, v)| (k.clone(), serde_json::json!(v)))
+                .collect();
+            print_json(&map);
+        }
+        Format::Tsv => {
+            println!("key\tset\tvalue");
+            for (k, v) in vars {
+                let value = v.as_deref().map(escape_value).unwrap_or_default();
+                println!("{}\t{}\t{}", k, v.is_some(), value);
+            }
+        }
+    }
+}
+
+/// Returns the pid of the main process of each service and of its logging subservice,
+/// which is named 'svc/log', 0 if there is none
+fn main_pids(mut sv: service::Service, names: &[String]) -> Vec<(String, u32)> {
+    let mut pids: Vec<(String, u32)> = Vec::new();
+
+    for name in names {
+        sv = rename(sv, name);
+
+        match service::FullStatus::new(&sv) {
+            Ok(s) => {
+                pids.push((name.clone(), s.service.pid()));
+                if let Some(log) = s.log {
+                    pids.push((format!("{}/log", name), log.pid()));
+                }
+                if let Some(e) = s.log_error {
+                    eprintln!(
+                        "Failed to get status of log service ({})! Error: {}",
+                        &sv.name, e,
+                    );
+                }
+            }
+            Err(e) => eprintln!(
+                "Failed to get status of service ({})! Error: {}",
+                &sv.name, e,
+            ),
+        }
+    }
+
+    pids
+}
+
+/// Prints the metrics of the enabled services, or writes them to a file
+///
+/// # Arguments
+///
+/// * `sv` - Service that is renamed to each enabled service
+/// * `conf` - Config holding lndir
+/// * `output` - Path of the file to write the metrics to, stdout if None
+fn write_metrics(mut sv: service::Service, conf: &configuration::Config, output: Option<&str>) {
+    let mut services: Vec<(String, Option<service::FullStatus>)> = Vec::new();
+
+    // Metrics without any service would look like every service went away
+    if let Err(e) = std::fs::read_dir(&conf.lndir) {
+        eprintln!("{}", errors::Error::Read(conf.lndir.clone(), e));
+        exit!(fail => 1);
+    }
+
+    for name in servicedir::show_active_services(conf).unwrap_or_default() {
+        sv = rename(sv, &name);
+        // A service that runsv doesn't supervise has no status to read
+        services.push((name, service::FullStatus::new(&sv).ok()));
+    }
+
+    let content = metrics::render(&services);
+
+    match output {
+        Some(p) => {
+            if let Err(e) = utils::write_atomic(std::path::Path::new(p), content.as_bytes()) {
+                eprintln!("{}", e);
+                exit!(fail => 1);
+            }
+        }
+        None => print!("{}", content),
+    }
+}
+
+/// Socket a service listens on and the process of the service that has it open
+#[derive(serde::Serialize)]
+struct Port {
+    /// Name of the service, 'svc/log' for a logging subservice
+    name: String,
+    #[serde(flatten)]
+    socket: sockets::Socket,
+    /// Pid of the process closest to the main one that has the socket open
+    pid: u32,
+    cmdline: String,
+}
+
+/// Prints the sockets that services listen on
+fn print_ports(sv: service::Service, names: &[String], format: Format) {
+    let root = std::path::Path::new(procfs::PROC);
+
+    let (parents, listening) =
+        match procfs::parents(root).and_then(|p| Ok((p, sockets::read(root)?))) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("{}", e);
+                exit!(fail => 1);
+            }
+        };
+
+    let mut ports: Vec<Port> = Vec::new();
+
+    for (name, pid) in main_pids(sv, names) {
+        if pid == 0 {
+            continue;
+        }
+
+        let mut seen: Vec<u64> = Vec::new();
+
+        // Children inherit the sockets of their parent, which is the one shown
+        for (_, pid) in procfs::tree(&parents, pid) {
+            for inode in sockets::inodes(root, pid) {
+                if seen.contains(&inode) {
+                    continue;
+                }
+                seen.push(inode);
+
+                if let Some(s) = listening.iter().find(|s| s.inode == inode) {
+                    ports.push(Port {
+                        name: name.clone(),
+                        socket: s.clone(),
+                        pid,
+                        cmdline: procfs::read(root, pid)
+                            .map(|p| p.cmdline)
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+        }
+    }
+
+    match format {
+        Format::Json => print_json(&ports),
+        Format::Tsv => {
+            println!("name\tproto\taddress\tport\tpid\tcmdline");
+            for p in &ports {
+                let port = p.socket.port.map(|n| n.to_string()).unwrap_or_default();
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    p.name, p.socket.proto, p.socket.address, port, p.pid, p.cmdline
+                );
+            }
+        }
+        Format::Text => {
+            println!(
+                "{:<20} {:<5} {:<24} {:>5} {:>7}  COMMAND",
+                "NAME", "PROTO", "ADDRESS", "PORT", "PID"
+            );
+            for p in &ports {
+                let port = p
+                    .socket
+                    .port
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| "-".into());
+                println!(
+                    "{:<20} {:<5} {:<24} {:>5} {:>7}  {}",
+                    p.name, p.socket.proto, p.socket.address, port, p.pid, p.cmdline
+                );
+            }
+        }
+    }
+}
+
+/// Processes of a service or of its logging subservice, with what they use together
+#[derive(serde::Serialize)]
+struct Usage {
+    /// Name of the service, 'svc/log' for a logging subservice
+    name: String,
+    /// Pid of the main process, 0 if there is none
+    pid: u32,
+    rss: u64,
+    cpu: f64,
+    threads: u32,
+    fds: usize,
+    /// The main process and its descendants, each followed by its children
+    processes: Vec<procfs::Process>,
+    /// Depth of each process below the main one
+    #[serde(skip)]
+    depths: Vec<usize>,
+}
+
+impl Usage {
+    fn new(name: String, pid: u32, parents: &[(u32, u32)]) -> Self {
+        let tree = if pid == 0 {
+            Vec::new()
+        } else {
+            procfs::read_tree(std::path::Path::new(procfs::PROC), parents, pid)
+        };
+
+        Self {
+            name,
+            pid,
+            rss: tree.iter().map(|(_, p)| p.rss).sum(),
+            cpu: tree.iter().map(|(_, p)| p.cpu).sum(),
+            threads: tree.iter().map(|(_, p)| p.threads).sum(),
+            fds: tree.iter().filter_map(|(_, p)| p.fds).sum(),
+            depths: tree.iter().map(|(d, _)| *d).collect(),
+            processes: tree.into_iter().map(|(_, p)| p).collect(),
+        }
+    }
+}
+
+/// Formats a number of bytes with a binary unit, like 12.3M
+fn format_bytes(b: u64) -> String {
+    let mut size = b as f64;
+
+    for unit in ["B", "K", "M", "G"].iter() {
+        if size < 1024.0 {
+            return if *unit == "B" {
+                format!("{}B", b)
+            } else {
+                format!("{:.1}{}", size, unit)
+            };
+        }
+        size /= 1024.0;
+    }
+
+    format!("{:.1}T", size)
+}
+
+/// Prints what the processes of services use, each process of each service if no
+/// sort is given like status --resources does, or else each service sorted like top
+fn print_resources(sv: service::Service, names: &[String], format: Format, sort: Option<&str>) {
+    let parents = match procfs::parents(std::path::Path::new(procfs::PROC)) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit!(fail => 1);
+        }
+    };
+
+    let mut usages: Vec<Usage> = main_pids(sv, names)
+        .into_iter()
+        .map(|(name, pid)| Usage::new(name, pid, &parents))
+        .collect();
+
+    match sort {
+        Some("cpu") => usages.sort_by(|a, b| b.cpu.total_cmp(&a.cpu)),
+        Some("threads") => usages.sort_by_key(|u| std::cmp::Reverse(u.threads)),
+        Some("fds") => usages.sort_by_key(|u| std::cmp::Reverse(u.fds)),
+        Some(_) => usages.sort_by_key(|u| std::cmp::Reverse(u.rss)),
+        None => (),
+    }
+
+    match format {
+        Format::Json => print_json(&usages),
+        Format::Tsv => {
+            println!("name\tpid\tppid\tdepth\trss\tcpu\tthreads\tfds\tcmdline");
+            for u in &usages {
+                for (p, depth) in u.processes.iter().zip(&u.depths) {
+                    let fds = p.fds.map(|f| f.to_string()).unwrap_or_default();
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}\t{:.2}\t{}\t{}\t{}",
+                        u.name, p.pid, p.ppid, depth, p.rss, p.cpu, p.threads, fds, p.cmdline
+                    );
+                }
+            }
+        }
+        Format::Text if sort.is_some() => {
+            println!(
+                "{:<20} {:>7} {:>5} {:>8} {:>10} {:>7} {:>5}  COMMAND",
+                "NAME", "PID", "PROCS", "RSS", "CPU", "THREADS", "FDS"
+            );
+            for u in &usages {
+                let cmd = u
+                    .processes
+                    .first()
+                    .map(|p| p.cmdline.as_str())
+                    .unwrap_or("");
+                println!(
+                    "{:<20} {:>7} {:>5} {:>8} {:>9.2}s {:>7} {:>5}  {}",
+                    u.name,
+                    u.pid,
+                    u.processes.len(),
+                    format_bytes(u.rss),
+                    u.cpu,
+                    u.threads,
+                    u.fds,
+                    cmd
+                );
+            }
+        }
+        Format::Text => {
+            for u in &usages {
+                if u.processes.is_empty() {
+                    println!("{}: no processes", u.name);
+                    continue;
+                }
+                println!(
+                    "{}: rss {}, cpu {:.2}s, threads {}, fds {}",
+                    u.name,
+                    format_bytes(u.rss),
+                    u.cpu,
+                    u.threads,
+                    u.fds
+                );
+                for (p, depth) in u.processes.iter().zip(&u.depths) {
+                    let fds = p.fds.map(|f| f.to_string()).unwrap_or_else(|| "?".into());
+                    println!(
+                        "  {:>7} {:>8} {:>9.2}s {:>4} {:>5}  {}{}",
+                        p.pid,
+                        format_bytes(p.rss),
+                        p.cpu,
+                        p.threads,
+                        fds,
+                        "  ".repeat(*depth),
+                        p.cmdline
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Parses the value of an argument, exits if it is given but invalid
+fn parse_arg<T: std::str::FromStr>(sub_m: &clap::ArgMatches, name: &str) -> Option<T> {
+    let value = sub_m.value_of(name)?;
+
+    match value.parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            eprintln!("ERROR: invalid value of --{}: {}", name, value);
+            exit!(fail => 1);
+        }
+    }
+}
+
+/// Sends the down command to a flapping service so runsv stops restarting it, returns
+/// whether it was sent
+fn bring_down(sv: &service::Service) -> bool {
+    match sv.signal("d") {
+        Ok(_) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Restart count of a service listed by flapping
+#[derive(serde::Serialize)]
+struct FlapRow {
+    name: String,
+    restarts: usize,
+    window: u64,
+    flapping: bool,
+    down: bool,
+    /// None if the status couldn't be read
+    status: Option<service::Status>,
+}
+
+/// Records the restarts of services in the history in statedir and lists those that
+/// are flapping, bringing them down if flap_down is set
+fn flapping(
+    mut sv: service::Service,
+    conf: &configuration::Config,
+    matches: clap::ArgMatches,
+    format: Format,
+) {
+    let sub_m = match matches.subcommand_matches("flapping") {
+        Some(s) => s,
+        None => return,
+    };
+
+    let enabled = servicedir::show_active_services(conf).unwrap_or_default();
+    let names: Vec<String> = match sub_m.values_of("services") {
+        Some(v) => v.map(|s| s.to_string()).collect(),
+        None => enabled.clone(),
+    };
+
+    let path = flapping::path(conf);
+    let mut history = match flapping::History::load(&path) {
+        Ok(h) => h,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit!(fail => 1);
+        }
+    };
+
+    let sample = parse_seconds(sub_m, "sample").unwrap_or_default();
+    let deadline = Instant::now() + sample;
+    let dirs: Vec<PathBuf> = names
+        .iter()
+        .map(|n| conf.lndir.join(n).join("supervise"))
+        .collect();
+    let mut watcher = watch::Watcher::new(&dirs, !sample.is_zero());
+    let stop = AtomicBool::new(false);
+
+    loop {
+        for name in &names {
+            sv = rename(sv, name);
+
+            let mut s = service::Status::default();
+            if s.status(&sv, false).is_ok() {
+                history.observe(name, &s);
+            }
+        }
+
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left == Duration::from_secs(0) {
+            break;
+        }
+        watcher.wait(left, &stop);
+    }
+
+    let now = SystemTime::now();
+
+    // Services given by name that aren't enabled are kept too
+    let mut kept = enabled;
+    kept.extend(names.iter().cloned());
+    history.prune(&kept, conf.flap_window, now);
+
+    // Flapping is still reported if the history can't be written
+    if let Err(e) = history.save(&path) {
+        eprintln!("{}", e);
+    }
+
+    let mut rows: Vec<FlapRow> = Vec::new();
+
+    for name in &names {
+        sv = rename(sv, name);
+
+        let restarts = history.restarts(name, conf.flap_window, now);
+        let flapping = restarts >= conf.flap_restarts;
+
+        if !flapping && !sub_m.is_present("all") {
+            continue;
+        }
+
+        let mut s = service::Status::default();
+        let status = match s.status(&sv, false) {
+            Ok(_) => Some(s),
+            Err(_) => None,
+        };
+        let wanted = status.as_ref().map(|s| s.want_up()).unwrap_or(false);
+        let down = flapping && conf.flap_down && wanted && bring_down(&sv);
+
+        rows.push(FlapRow {
+            name: name.clone(),
+            restarts,
+            window: conf.flap_window,
+            flapping,
+            down,
+            status,
+        });
+    }
+
+    match format {
+        Format::Text => {
+            for r in &rows {
+                let mut line = format!(
+                    "{}: restarted {} times in {}s",
+                    r.name, r.restarts, r.window
+                );
+                if let Some(s) = &r.status {
+                    line.push_str(&format!(", {} {}s", s.state(), s.seconds()));
+                }
+                if r.down {
+                    line.push_str(", brought down");
+                }
+                println!("{}", line);
+            }
+        }
+        Format::Json => print_json(&rows),
+        Format::Tsv => {
+            println!("name\trestarts\twindow\tflapping\tdown\tstate\tpid\tseconds");
+            for r in &rows {
+                let (state, pid, seconds) = match &r.status {
+                    Some(s) => (s.state().to_string(), s.pid(), s.seconds()),
+                    None => (String::new(), 0, 0),
+                };
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    r.name, r.restarts, r.window, r.flapping, r.down, state, pid, seconds
+                );
+            }
+        }
+    }
+
+    if rows.iter().any(|r| r.flapping) {
+        exit!(fail => 1);
+    }
+}
+
+/// Change of a watched service, the name is 'svc/log' for a logging subservice
+struct WatchEvent {
+    time: SystemTime,
+    name: String,
+    change: watch::Change,
+}
+
+/// Returns the seconds given to an argument as a Duration, None if it isn't given,
+/// exits if they aren't a number from 0 to `configuration::MAX_SECONDS`
+fn parse_seconds(sub_m: &clap::ArgMatches, name: &str) -> Option<Duration> {
+    let value = sub_m.value_of(name)?;
+
+    match value.parse::<f64>() {
+        Ok(n) if (0.0..=configuration::MAX_SECONDS as f64).contains(&n) => {
+            Some(Duration::from_secs_f64(n))
+        }
+        _ => {
+            eprintln!(
+                "ERROR: --{} must be a number of seconds from 0 to {}: {}",
+                name,
+                configuration::MAX_SECONDS,
+                value
+            );
+            exit!(fail => 1);
+        }
+    }
+}
+
+/// Maximum number of changes listed below the status of the services by watch
+const WATCH_EVENTS: usize = 10;
+
+/// Shows the status of services each time it changes until SIGINT is received
+fn watch_services(
+    mut sv: service::Service,
+    conf: &configuration::Config,
+    matches: clap::ArgMatches,
+    format: Format,
+) {
+    let sub_m = match matches.subcommand_matches("watch") {
+        Some(s) => s,
+        None => return,
+    };
+
+    let interval = match parse_seconds(sub_m, "interval") {
+        Some(d) if !d.is_zero() => d,
+        _ => {
+            eprintln!("ERROR: --interval must be a positive number of seconds");
+            exit!(fail => 1);
+        }
+    };
+
+    let given: Option<Vec<String>> = sub_m
+        .values_of("services")
+        .map(|v| v.map(|s| s.to_string()).collect());
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler = Arc::clone(&stop);
+
+    // Without a handler Ctrl-C still stops watch, only less cleanly
+    let _ = ctrlc::set_handler(move || handler.store(true, Ordering::SeqCst));
+
+    let redraw = format == Format::Text && std::io::stdout().is_terminal();
+    let window = Duration::from_secs(conf.flap_window);
+    let mut tracker = watch::Tracker::new(window);
+    let mut events: VecDeque<WatchEvent> = VecDeque::new();
+    let mut names: Vec<String> = Vec::new();
+    let mut watcher = watch::Watcher::new::<PathBuf>(&[], false);
+
+    if format == Format::Tsv {
+        println!("time\tname\tevent\tfrom\tto");
+    }
+
+    while !stop.load(Ordering::SeqCst) {
+        // Services enabled meanwhile are watched too
+        let current = match &given {
+            Some(g) => g.clone(),
+            None => servicedir::show_active_services(conf).unwrap_or_default(),
+        };
+
+        if current != names {
+            names = current;
+            let dirs: Vec<PathBuf> = names
+                .iter()
+                .flat_map(|n| {
+                    let dir = conf.lndir.join(n);
+                    vec![dir.join("supervise"), dir.join("log/supervise")]
+                })
+                .collect();
+            watcher = watch::Watcher::new(&dirs, !sub_m.is_present("no-inotify"));
+        }
+
+        let now = SystemTime::now();
+        let mut lines: Vec<String> = Vec::new();
+        let mut new: Vec<WatchEvent> = Vec::new();
+
+        for name in &names {
+            sv = rename(sv, name);
+
+            let s = match service::FullStatus::new(&sv) {
+                Ok(s) => s,
+                Err(e) => {
+                    lines.push(format!("{}: {}", name, e));
+                    continue;
+                }
+            };
+
+            let mut line = s.service.to_string();
+            let mut changed = false;
+
+            for c in tracker.update(name, s.service, now) {
+                changed = true;
+                new.push(WatchEvent {
+                    time: now,
+                    name: name.clone(),
+                    change: c,
+                });
+            }
+
+            if let Some(e) = &s.log_error {
+                line.push_str(&format!("; log: {}", e));
+            }
+
+            if let Some(log) = s.log {
+                line.push_str(&format!("; {}", log));
+
+                let log_name = format!("{}/log", name);
+
+                for c in tracker.update(&log_name, log, now) {
+                    changed = true;
+                    new.push(WatchEvent {
+                        time: now,
+                        name: log_name.clone(),
+                        change: c,
+                    });
+                }
+            }
+
+            let restarts = tracker.restarts(name, now);
+
+            if restarts >= conf.flap_restarts {
+                let wanted = tracker.last(name).map(|s| s.want_up()).unwrap_or(false);
+
+                if conf.flap_down && wanted && bring_down(&sv) {
+                    line.push_str(", brought down");
+                }
+
+                line = format!(
+                    "\x1b[1;31m{}, restarted {} times in {}s\x1b[0m",
+                    line, restarts, conf.flap_window
+                );
+            } else if changed {
+                line = format!("\x1b[1;33m{}\x1b[0m", line);
+            }
+
+            lines.push(line);
+        }
+
+        if redraw {
+            events.extend(new);
+            while events.len() > WATCH_EVENTS {
+                events.pop_front();
+            }
+
+            // Move to the top left and clear the screen
+            print!("\x1b[H\x1b[2J");
+            println!(
+                "every {}s{}, Ctrl-C to quit\n",
+                interval.as_secs_f64(),
+                if watcher.uses_inotify() {
+                    " and on changes"
+                } else {
+                    ""
+                }
+            );
+            for l in &lines {
+                println!("{}", l);
+            }
+            if !events.is_empty() {
+                println!();
+                for e in &events {
+                    print_watch_event(e, format);
+                }
+            }
+        } else {
+            for e in &new {
+                print_watch_event(e, format);
+            }
+        }
+
+        watcher.wait(interval, &stop);
+    }
+
+    if redraw {
+        println!();
+    }
+}
+
+/// Prints a change of a watched service as text, a JSON object or a TSV row
+fn print_watch_event(e: &WatchEvent, format: Format) {
+    match format {
+        Format::Text => {
+            // Only the time of the day
+            let time = tai64::format_utc(e.time);
+            println!("{} {}: {}", &time[11..19], e.name, e.change);
+        }
+        Format::Json => {
+            let mut v = serde_json::to_value(&e.change).unwrap_or_default();
+            if let Some(m) = v.as_object_mut() {
+                let secs = e.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                m.insert("time".to_string(), serde_json::json!(secs.as_secs()));
+                m.insert("name".to_string(), serde_json::json!(e.name));
+            }
+            print_json(&v);
+        }
+        Format::Tsv => {
+            let secs = e.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+            let (event, from, to) = match &e.change {
+                watch::Change::State { from, to } => ("state", from.to_string(), to.to_string()),
+                watch::Change::Pid { from, to } => ("pid", from.to_string(), to.to_string()),
+                watch::Change::Restart => ("restart", String::new(), String::new()),
+            };
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                secs.as_secs(),
+                e.name,
+                event,
+                from,
+                to
+            );
+        }
+    }
+}
+
+/// Prints the log of a service and keeps following it if asked to
+fn show_log(mut sv: service::Service, conf: &configuration::Config, matches: clap::ArgMatches) {
+    if let Some(sub_m) = matches.subcommand_matches("log") {
+        if let Some(name) = sub_m.value_of("service") {
+            sv = rename(sv, name);
+        }
+
+        let n: Option<usize> = match sub_m.value_of("lines") {
+            Some(e) => match e.parse::<usize>() {
+                Ok(n) => Some(n),
+                Err(e) => {
+                    eprintln!("ERROR: invalid value for --lines! Error: {}", e);
+                    exit!(fail => 1);
+                }
+            },
+            None => None,
+        };
+
+        let dir = match log::find_logdir(conf, &sv) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("{}", e);
+                exit!(fail => 1);
+            }
+        };
+
+        let mut follower = match log::tail(&dir, n) {
+            Ok((lines, f)) => {
+                for line in lines {
+                    println!("{}", log::decode_line(&line));
+                }
+                f
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                exit!(fail => 1);
+            }
+        };
+
+        while sub_m.is_present("follow") {
+            std::thread::sleep(Duration::from_millis(250));
+
+            match follower.poll() {
+                Ok(lines) => {
+                    for line in lines {
+                        println!("{}", log::decode_line(&line));
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    exit!(fail => 1);
+                }
+            }
+        }
+    }
+}
+
+/// Disables the services given to disable, stopping them first unless `inactive`
+/// is true, in which case they are only removed from lndir
+///
+/// # Arguments
+///
+/// * `wait` - Time to wait for each stop step given by --wait, stop_timeout if None
+fn disable_services(
+    mut sv: service::Service,
+    conf: &configuration::Config,
+    matches: clap::ArgMatches,
+    inactive: bool,
+    wait: Option<Duration>,
+) {
+    let wait = wait.unwrap_or_else(|| Duration::from_secs(conf.stop_timeout));
+
+    if let Some(sub_m) = matches.subcommand_matches("disable") {
+        let order = order_of(conf, sub_m, true);
+        let mut failed: Vec<String> = Vec::new();
+
+        for name in &order.names {
+            // Services that depend on this one must be disabled first
+            if let Some(dep) = order.failed_before(name, &failed) {
+                eprintln!("{}", Error::DepFailed(name.clone(), dep.clone()));
+                failed.push(name.clone());
+                continue;
+            }
+
+            sv = rename(sv, name);
+
+            if inactive {
+                match sv.unlink() {
+                    Ok(_) => println!("service '{}' disabled", name),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        failed.push(name.clone());
+                    }
+                }
+                continue;
+            }
+
+            match &sv.disable_with(wait, conf.stop_escalate) {
+                Ok(service::StopStep::Down) => println!("service '{}' disabled", name),
+                Ok(step) => println!("service '{}' disabled after sending {}", name, step),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    failed.push(name.clone());
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            exit!(fail => 1);
+        }
+    }
+}
+
+fn enable_services(
+    mut sv: service::Service,
+    conf: &configuration::Config,
+    matches: clap::ArgMatches,
+) {
+    if let Some(sub_m) = matches.subcommand_matches("enable") {
+        let order = order_of(conf, sub_m, false);
+        let asked: Vec<&str> = match sub_m.values_of("services") {
+            Some(v) => v.collect(),
+            None => Vec::new(),
+        };
+        let mut failed: Vec<String> = Vec::new();
+
+        for name in &order.names {
+            // Services this one depends on must be enabled first
+            if let Some(dep) = order.failed_before(name, &failed) {
+                eprintln!("{}", Error::DepFailed(name.clone(), dep.clone()));
+                failed.push(name.clone());
+                continue;
+            }
+
+            sv = rename(sv, name);
+
+            // Only the directories about to be linked, enable refuses the others
+            if !sub_m.is_present("no-check") && !sv.is_linked() {
+                let lints = lint::check_dir(&conf.svdir.join(name));
+
+                for l in &lints {
+                    eprintln!("{}: {}", name, l);
+                }
+
+                if lint::has_errors(&lints) {
+                    let errors = lints
+                        .iter()
+                        .filter(|l| l.severity == lint::Severity::Error)
+                        .count();
+                    eprintln!("{}", Error::Invalid(name.clone(), errors));
+                    failed.push(name.clone());
+                    continue;
+                }
+            }
+
+            match &sv.enable() {
+                Ok(_) => println!("service '{}' enabled", name),
+                // A dependency that is already enabled is what we want
+                Err(Error::Enabled(_)) if !asked.contains(&name.as_str()) => (),
+                Err(e @ Error::Enabled(_)) => eprintln!("{}", e),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    failed.push(name.clone());
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            exit!(fail => 1);
+        }
+    }
 }
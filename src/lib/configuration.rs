@@ -1,25 +1,233 @@
 use crate::errors::Error;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env::VarError;
 use std::fmt::Result as fmtResult;
 use std::fmt::{Display, Formatter};
 use std::io::Read;
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Most seconds svctrl waits for anything, a day, longer waits are rejected as
+/// their deadlines could overflow
+pub const MAX_SECONDS: u64 = 86_400;
+
+/// Keys of the configuration, in the order they are printed
+pub const KEYS: &[&str] = &[
+    "svdir",
+    "lndir",
+    "runsvdir",
+    "maskdir",
+    "templatedir",
+    "logdir",
+    "stop_timeout",
+    "stop_escalate",
+    "statedir",
+    "flap_restarts",
+    "flap_window",
+    "flap_down",
+];
+
+/// Where a value of the configuration was taken from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// The value wasn't set anywhere
+    Default,
+    /// The value was set in the configuration file at the path
+    File(PathBuf),
+    /// The value was set by the environment variable of the key, see `env_var`
+    Env,
+    /// The value was set by a flag on the command line
+    Cli,
+}
+
+/// `fmt::Display` for Source, used by config --explain
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(p) => write!(f, "{}", p.display()),
+            Source::Env => write!(f, "environment"),
+            Source::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// Returns the name of the environment variable that sets a key of the configuration,
+/// which is the key in upper case prefixed with SVCTRL_
+///
+/// # Remarks
+///
+/// Every key in `KEYS` has one, like SVCTRL_SVDIR, SVCTRL_LNDIR or SVCTRL_STOP_TIMEOUT.
+/// Booleans are written as true or false. SVCTRL_CONFIG holds the path of a
+/// configuration file, see `Config::set_conf`.
+pub fn env_var(key: &str) -> String {
+    format!("SVCTRL_{}", key.to_uppercase())
+}
+
+/// Number of seconds from 1 to `MAX_SECONDS`, used for the times of a layer
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "u64")]
+pub struct Seconds(u64);
+
+impl Seconds {
+    /// Returns the number of seconds
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl TryFrom<u64> for Seconds {
+    type Error = Error;
+
+    fn try_from(n: u64) -> Result<Self, Self::Error> {
+        if n == 0 || n > MAX_SECONDS {
+            return Err(Error::InvalidSeconds(n.to_string()));
+        }
+
+        Ok(Self(n))
+    }
+}
+
+impl FromStr for Seconds {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<u64>() {
+            Ok(n) => Self::try_from(n),
+            Err(_) => Err(Error::InvalidSeconds(s.to_string())),
+        }
+    }
+}
+
+/// Values of the configuration set by one of the places it is read from, each of
+/// them may set only some of the keys
+///
+/// # Remarks
+///
+/// The layers are applied from lowest to highest precedence, see `Config::set_conf`.
+#[derive(Debug, Deserialize, Default)]
+pub struct Layer {
+    /// See `Config::svdir`
+    pub svdir: Option<PathBuf>,
+    /// See `Config::lndir`
+    pub lndir: Option<PathBuf>,
+    /// See `Config::runsvdir`
+    pub runsvdir: Option<PathBuf>,
+    /// See `Config::maskdir`
+    pub maskdir: Option<PathBuf>,
+    /// See `Config::templatedir`
+    pub templatedir: Option<PathBuf>,
+    /// See `Config::logdir`
+    pub logdir: Option<PathBuf>,
+    /// See `Config::stop_timeout`
+    pub stop_timeout: Option<Seconds>,
+    /// See `Config::stop_escalate`
+    pub stop_escalate: Option<bool>,
+    /// See `Config::statedir`
+    pub statedir: Option<PathBuf>,
+    /// See `Config::flap_restarts`, 0 is rejected as every service would be flapping
+    pub flap_restarts: Option<NonZeroUsize>,
+    /// See `Config::flap_window`, 0 is rejected as no restart would be counted
+    pub flap_window: Option<NonZeroU64>,
+    /// See `Config::flap_down`
+    pub flap_down: Option<bool>,
+}
+
+impl Layer {
+    /// Returns the layer set by the environment variables of each key, an empty
+    /// variable is the same as one that isn't set
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_lookup(env_lookup)
+    }
+
+    /// Returns the layer set by variables named like the environment variable of
+    /// each key, see `Layer::from_env`
+    ///
+    /// # Arguments
+    ///
+    /// * `lookup` - Function returning the value of a variable by its name, like
+    ///   std::env::var
+    pub fn from_lookup<F>(lookup: F) -> Result<Self, Error>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        Ok(Self {
+            svdir: var(&lookup, "svdir")?,
+            lndir: var(&lookup, "lndir")?,
+            runsvdir: var(&lookup, "runsvdir")?,
+            maskdir: var(&lookup, "maskdir")?,
+            templatedir: var(&lookup, "templatedir")?,
+            logdir: var(&lookup, "logdir")?,
+            stop_timeout: var(&lookup, "stop_timeout")?,
+            stop_escalate: var(&lookup, "stop_escalate")?,
+            statedir: var(&lookup, "statedir")?,
+            flap_restarts: var(&lookup, "flap_restarts")?,
+            flap_window: var(&lookup, "flap_window")?,
+            flap_down: var(&lookup, "flap_down")?,
+        })
+    }
+}
+
+/// Looks up a variable in the environment of svctrl
+fn env_lookup(name: &str) -> Result<String, VarError> {
+    std::env::var(name)
+}
+
+/// Parses the variable of a key, see `env_var`
+fn var<T, F>(lookup: &F, key: &str) -> Result<Option<T>, Error>
+where
+    T: FromStr,
+    T::Err: Display,
+    F: Fn(&str) -> Result<String, VarError>,
+{
+    let name = env_var(key);
+
+    match lookup(&name) {
+        Ok(ref v) if v.is_empty() => Ok(None),
+        Ok(v) => match v.parse() {
+            Ok(t) => Ok(Some(t)),
+            Err(e) => Err(Error::EnvVar(name, e.to_string())),
+        },
+        Err(VarError::NotPresent) => Ok(None),
+        Err(e) => Err(Error::EnvVar(name, e.to_string())),
+    }
+}
 
 /// `fmt::Display` for Config, showing in the TOML format the configuration is written in
 impl Display for Config {
     fn fmt(&self, f: &mut Formatter) -> fmtResult {
-        if self.path.is_some() {
-            writeln!(f, "path = '{}'", self.path.as_ref().unwrap().display());
+        if let Some(path) = &self.path {
+            writeln!(f, "path = '{}'", path.display())?;
+        }
+        writeln!(f, "svdir = '{}'", self.svdir.display())?;
+        writeln!(f, "lndir = '{}'", self.lndir.display())?;
+        writeln!(f, "runsvdir = '{}'", self.runsvdir.display())?;
+        writeln!(f, "maskdir = '{}'", self.maskdir.display())?;
+        writeln!(f, "templatedir = '{}'", self.templatedir.display())?;
+        if let Some(logdir) = &self.logdir {
+            writeln!(f, "logdir = '{}'", logdir.display())?;
         }
-        writeln!(f, "svdir = '{}'", self.svdir.display());
-        write!(f, "lndir = '{}'", self.lndir.display())
+        writeln!(f, "stop_timeout = {}", self.stop_timeout)?;
+        writeln!(f, "stop_escalate = {}", self.stop_escalate)?;
+        writeln!(f, "statedir = '{}'", self.statedir.display())?;
+        writeln!(f, "flap_restarts = {}", self.flap_restarts)?;
+        writeln!(f, "flap_window = {}", self.flap_window)?;
+        write!(f, "flap_down = {}", self.flap_down)
     }
 }
 
 /// Holds the location of a config as path and all the values that can be used
 /// in the config
-#[derive(Debug, Deserialize, Clone)]
+///
+/// # Remarks
+///
+/// Serializing a Config gives an object with every key of the configuration file,
+/// logdir is null when it isn't set. The path of the file isn't serialized.
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     /// Path to where the config is located for opening, reading and writing
     /// it is an option because it can be set to None in which case it is
@@ -31,6 +239,88 @@ pub struct Config {
     pub svdir: PathBuf, // Path to where directories live
     /// Path where the services can be linked to show they are activated
     pub lndir: PathBuf, // Path to where directories are linked to
+    /// Path where the runlevels are, each a directory where services are linked to
+    /// with a symlink called current pointing at the one in use
+    #[serde(default = "default_runsvdir")]
+    pub runsvdir: PathBuf,
+    /// Path where a file named after each masked service is kept, masked services
+    /// can't be enabled or brought up
+    #[serde(default = "default_maskdir")]
+    pub maskdir: PathBuf,
+    /// Path where the templates of the scripts written by svctrl new are looked for,
+    /// the built in ones are used for those that aren't there
+    #[serde(default = "default_templatedir")]
+    pub templatedir: PathBuf,
+    /// Path where svlogd of each service logs to in a directory named after the
+    /// service, used when the log directory can't be found in log/run
+    #[serde(default)]
+    pub logdir: Option<PathBuf>,
+    /// Seconds that stopping a service waits for it to go down, for each step, from 1
+    /// to `MAX_SECONDS`
+    #[serde(default = "default_stop_timeout")]
+    pub stop_timeout: u64,
+    /// Whether stopping a service that didn't go down in time sends it TERM and
+    /// then KILL instead of giving up
+    #[serde(default)]
+    pub stop_escalate: bool,
+    /// Path where svctrl keeps what it needs to remember between runs, like the
+    /// restarts of each service seen by flapping
+    #[serde(default = "default_statedir")]
+    pub statedir: PathBuf,
+    /// Restarts within flap_window after which a service is flapping
+    #[serde(default = "default_flap_restarts")]
+    pub flap_restarts: usize,
+    /// Seconds in which the restarts of a service are counted
+    #[serde(default = "default_flap_window")]
+    pub flap_window: u64,
+    /// Whether flapping services are brought down by sending them the down command,
+    /// so runsv stops restarting them
+    #[serde(default)]
+    pub flap_down: bool,
+    /// Whether this is the configuration of the services of the user running svctrl
+    /// under their own runsvdir, which changes where the configuration is searched
+    /// for. This isn't in the config either
+    #[serde(skip)]
+    pub user: bool,
+    /// Where each key of the configuration was taken from, keys that aren't in it
+    /// have their default value
+    #[serde(skip)]
+    pub sources: HashMap<String, Source>,
+}
+
+/// Default value of runsvdir, where Void Linux keeps its runlevels
+fn default_runsvdir() -> PathBuf {
+    PathBuf::from("/etc/runit/runsvdir")
+}
+
+/// Default value of maskdir
+fn default_maskdir() -> PathBuf {
+    PathBuf::from("/etc/svctrl/masked")
+}
+
+/// Default value of templatedir
+fn default_templatedir() -> PathBuf {
+    PathBuf::from("/etc/svctrl/templates")
+}
+
+/// Default value of stop_timeout, the same time sv waits for
+fn default_stop_timeout() -> u64 {
+    7
+}
+
+/// Default value of statedir
+fn default_statedir() -> PathBuf {
+    PathBuf::from("/run/svctrl")
+}
+
+/// Default value of flap_restarts
+fn default_flap_restarts() -> usize {
+    5
+}
+
+/// Default value of flap_window
+fn default_flap_window() -> u64 {
+    60
 }
 
 /// Implements default values for upstream configuration, distributions should
@@ -41,12 +331,25 @@ impl Default for Config {
             path: None,
             svdir: PathBuf::from("/etc/sv"),
             lndir: PathBuf::from("/var/service"),
+            runsvdir: default_runsvdir(),
+            maskdir: default_maskdir(),
+            templatedir: default_templatedir(),
+            logdir: None,
+            stop_timeout: default_stop_timeout(),
+            stop_escalate: false,
+            statedir: default_statedir(),
+            flap_restarts: default_flap_restarts(),
+            flap_window: default_flap_window(),
+            flap_down: false,
+            user: false,
+            sources: HashMap::new(),
         }
     }
 }
 
 impl Config {
-    /// Deserializes a TOML config for svctrl and returns a Config struct with the values given
+    /// Deserializes a TOML config for svctrl and applies the values given on top of the
+    /// ones already in the struct
     ///
     /// # Example
     ///
@@ -74,12 +377,84 @@ impl Config {
 
         config_file.read_to_string(&mut config_string)?;
 
-        let config_toml: Self = toml::from_str(&config_string)?;
+        let layer: Layer = toml::from_str(&config_string)?;
+        let source = Source::File(path.clone());
 
-        self.svdir = config_toml.svdir;
-        self.lndir = config_toml.lndir;
+        Ok(self.apply(layer, source))
+    }
 
-        Ok(self)
+    /// Sets the values of the configuration that a layer sets and records where
+    /// they came from
+    ///
+    /// # Arguments
+    ///
+    /// * `l` - Layer holding the values to set
+    /// * `s` - Source of the values of the layer
+    pub fn apply(&mut self, l: Layer, s: Source) -> &mut Self {
+        let mut set: Vec<&str> = Vec::new();
+
+        if let Some(v) = l.svdir {
+            self.svdir = v;
+            set.push("svdir");
+        }
+        if let Some(v) = l.lndir {
+            self.lndir = v;
+            set.push("lndir");
+        }
+        if let Some(v) = l.runsvdir {
+            self.runsvdir = v;
+            set.push("runsvdir");
+        }
+        if let Some(v) = l.maskdir {
+            self.maskdir = v;
+            set.push("maskdir");
+        }
+        if let Some(v) = l.templatedir {
+            self.templatedir = v;
+            set.push("templatedir");
+        }
+        if let Some(v) = l.logdir {
+            self.logdir = Some(v);
+            set.push("logdir");
+        }
+        if let Some(v) = l.stop_timeout {
+            self.stop_timeout = v.get();
+            set.push("stop_timeout");
+        }
+        if let Some(v) = l.stop_escalate {
+            self.stop_escalate = v;
+            set.push("stop_escalate");
+        }
+        if let Some(v) = l.statedir {
+            self.statedir = v;
+            set.push("statedir");
+        }
+        if let Some(v) = l.flap_restarts {
+            self.flap_restarts = v.get();
+            set.push("flap_restarts");
+        }
+        if let Some(v) = l.flap_window {
+            self.flap_window = v.get();
+            set.push("flap_window");
+        }
+        if let Some(v) = l.flap_down {
+            self.flap_down = v;
+            set.push("flap_down");
+        }
+
+        for key in set {
+            self.sources.insert(key.to_string(), s.clone());
+        }
+
+        self
+    }
+
+    /// Returns where the value of a key of the configuration was taken from
+    pub fn source(&self, key: &str) -> Source {
+        match self.sources.get(key) {
+            Some(s) => s.clone(),
+            None => Source::Default,
+        }
     }
 
     /// Impleentation of new for Config, uses the default values
@@ -87,35 +462,79 @@ impl Config {
         Self::default()
     }
 
-    /// Returns an Option holding a `PathBuf` which is where the config was located or none if none
-    /// is found
+    /// Returns the default configuration for the services of a user, which live in
+    /// ~/.local/share/sv and are enabled in ~/.local/service, are masked in
+    /// ~/.local/share/svctrl/masked and are created from ~/.local/share/svctrl/templates,
+    /// with the state of svctrl in ~/.local/state/svctrl
+    ///
+    /// # Arguments
+    ///
+    /// * `home` - Path of the home directory of the user
+    pub fn user_in(home: &Path) -> Self {
+        Self {
+            svdir: home.join(".local/share/sv"),
+            lndir: home.join(".local/service"),
+            maskdir: home.join(".local/share/svctrl/masked"),
+            templatedir: home.join(".local/share/svctrl/templates"),
+            statedir: home.join(".local/state/svctrl"),
+            user: true,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the default configuration for the services of the user running svctrl,
+    /// see `Config::user_in`, the home directory is taken from $HOME
+    pub fn user() -> Result<Self, Error> {
+        match std::env::var_os("HOME") {
+            Some(h) => Ok(Self::user_in(Path::new(&h))),
+            None => Err(Error::NoHome),
+        }
+    }
+
+    /// Returns the path of the configuration of the user running svctrl, which is
+    /// svctrl/config.toml in $XDG_CONFIG_HOME or else in ~/.config
+    pub fn user_conf_path() -> Option<PathBuf> {
+        // The spec says relative paths are invalid and must be ignored
+        if let Some(p) = std::env::var_os("XDG_CONFIG_HOME") {
+            let p = PathBuf::from(p);
+            if p.is_absolute() {
+                return Some(p.join("svctrl/config.toml"));
+            }
+        }
+
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config/svctrl/config.toml"))
+    }
+
+    /// Returns a vector of the configuration files that exist, from the lowest to the
+    /// highest precedence
     ///
     /// The function searches for the config in 3 system locations suffixed with svctrl/config.toml:
-    /// - /run for temporary system configuration, /run is usually a tmpfs
-    /// - /etc for local administrator configuration
     /// - /usr/share for configuration from the distro
+    /// - /etc for local administrator configuration
+    /// - /run for temporary system configuration, /run is usually a tmpfs
+    ///
+    /// If the configuration is for the services of a user only the path given by
+    /// `Config::user_conf_path` is searched.
     ///
     /// # Examples
     ///
     /// ```
-    /// if let Some(c) = find() {
-    ///     println!("Found config on {}!", c);
+    /// for c in conf.find_conf() {
+    ///     println!("Found config on {}!", c.display());
     /// }
     /// ```
-    pub fn find_conf(&mut self) {
-        let paths = vec![
-            Path::new("/run/svctrl/config.toml"),
-            Path::new("/etc/svctrl/config.toml"),
-            Path::new("/usr/share/svctrl/config.toml"),
-        ];
-
-        for path in &paths {
-            if path.is_file() {
-                self.path = Some(path.to_path_buf());
-                return;
-            }
-        }
-        self.path = None;
+    pub fn find_conf(&self) -> Vec<PathBuf> {
+        let paths = if self.user {
+            Self::user_conf_path().into_iter().collect()
+        } else {
+            vec![
+                PathBuf::from("/usr/share/svctrl/config.toml"),
+                PathBuf::from("/etc/svctrl/config.toml"),
+                PathBuf::from("/run/svctrl/config.toml"),
+            ]
+        };
+
+        paths.into_iter().filter(|p| p.is_file()).collect()
     }
 
     /// Takes a configuration::Config struct and tries to load configuration from .path
@@ -135,34 +554,239 @@ impl Config {
         }
     }
 
-    /// Takes a configuration::Config struct and tries to find a configuration with .find_path()
-    /// unless a value was given for configuration, then use .open() to load the configuration
-    /// and then return it.
+    /// Takes a configuration::Config struct and loads every layer of the configuration
+    /// on top of it, each layer overriding the values set by the ones before it:
+    /// - every configuration file found by .find_conf(), from lowest to highest precedence
+    /// - the environment variables of each key, see `env_var`
+    /// - the configuration file given as conf_path, like with '-c' '--config', or else
+    ///   the one in $SVCTRL_CONFIG
     ///
-    /// If .find_conf() returns None for the .path value then we hope
+    /// Flags on the command line are applied by the caller afterwards with .apply().
+    /// The path field is left pointing at the last file that was loaded.
     pub fn set_conf(&mut self, conf_path: Option<PathBuf>) -> Result<&mut Self, Error> {
-        // Try to find path, if conf_path isn't None then it will be used instead
-        // and returned with success, no checks on whether the file exists which
-        // doesn't matter because it will be caught by self.load_conf()
-        match conf_path {
-            Some(conf_path) => self.path = Some(conf_path),
-            None => self.find_conf(),
-        }
-
-        // Tries to load configuration from .path field of the given struct it is
-        // guaranteed to be set by calling find_path before it, it will most
-        // likely error out when the user passes a configuration PathBuf via conf_path
-        // that doesn't actually exist
-        //
-        // In the case of svctrl the user can pass '-c' '--config' to an inexistant
-        // file.
-        if self.path.is_some() {
-            match self.load_conf() {
-                Ok(_) => (),
-                Err(e) => return Err(e),
-            }
+        let files = self.find_conf();
+
+        self.load_layers(files, conf_path, env_lookup)
+    }
+
+    /// Loads the layers of `Config::set_conf` from the configuration files given and
+    /// the variables looked up, see `Layer::from_lookup`
+    fn load_layers<F>(
+        &mut self,
+        files: Vec<PathBuf>,
+        conf_path: Option<PathBuf>,
+        lookup: F,
+    ) -> Result<&mut Self, Error>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        for path in files {
+            self.path = Some(path);
+            self.load_conf()?;
+        }
+
+        self.apply(Layer::from_lookup(&lookup)?, Source::Env);
+
+        let conf_path = match conf_path {
+            Some(p) => Some(p),
+            // SVCTRL_CONFIG
+            None => var::<PathBuf, _>(&lookup, "config")?,
+        };
+
+        // No checks on whether the file exists which doesn't matter because it
+        // will be caught by self.load_conf(), in the case of svctrl the user can
+        // pass '-c' '--config' to an inexistant file.
+        if let Some(conf_path) = conf_path {
+            self.path = Some(conf_path);
+            self.load_conf()?;
         }
 
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_open_partial() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("config.toml");
+
+        fs::write(&path, "lndir = '/run/service'\nstop_timeout = 30\n").unwrap();
+
+        let mut c = Config {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        c.load_conf().unwrap();
+
+        assert_eq!(c.svdir, PathBuf::from("/etc/sv"));
+        assert_eq!(c.lndir, PathBuf::from("/run/service"));
+        assert_eq!(c.stop_timeout, 30);
+        assert_eq!(c.source("svdir"), Source::Default);
+        assert_eq!(c.source("lndir"), Source::File(path));
+    }
+
+    #[test]
+    fn test_apply_layers() {
+        let mut c = Config::default();
+
+        c.apply(
+            Layer {
+                svdir: Some(PathBuf::from("/usr/share/sv")),
+                stop_timeout: Some(Seconds(10)),
+                ..Default::default()
+            },
+            Source::File(PathBuf::from("/usr/share/svctrl/config.toml")),
+        );
+        c.apply(
+            Layer {
+                stop_timeout: Some(Seconds(20)),
+                ..Default::default()
+            },
+            Source::Cli,
+        );
+
+        assert_eq!(c.svdir, PathBuf::from("/usr/share/sv"));
+        assert_eq!(c.stop_timeout, 20);
+        assert_eq!(
+            c.source("svdir"),
+            Source::File(PathBuf::from("/usr/share/svctrl/config.toml"))
+        );
+        assert_eq!(c.source("stop_timeout"), Source::Cli);
+        assert_eq!(c.source("logdir"), Source::Default);
+    }
+
+    /// Returns a lookup of variables in a map instead of the environment
+    fn lookup(vars: &HashMap<String, String>) -> impl Fn(&str) -> Result<String, VarError> + '_ {
+        move |name| vars.get(name).cloned().ok_or(VarError::NotPresent)
+    }
+
+    #[test]
+    fn test_env() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let env_conf = tmpdir.path().join("env.toml");
+        let cli_conf = tmpdir.path().join("cli.toml");
+
+        fs::write(&env_conf, "stop_timeout = 20\nlogdir = '/var/log/env'\n").unwrap();
+        fs::write(&cli_conf, "stop_timeout = 30\n").unwrap();
+
+        // Every key has a variable
+        let mut vars: HashMap<String, String> = HashMap::new();
+        for key in KEYS {
+            let value = if key.ends_with("escalate") || key.ends_with("down") {
+                "true"
+            } else {
+                "1"
+            };
+            vars.insert(env_var(key), value.to_string());
+        }
+
+        let mut c = Config::default();
+        c.load_layers(Vec::new(), None, lookup(&vars)).unwrap();
+
+        for key in KEYS {
+            assert_eq!(c.source(key), Source::Env, "{}", key);
+        }
+        assert_eq!(c.svdir, PathBuf::from("1"));
+        assert_eq!(c.stop_timeout, 1);
+        assert!(c.stop_escalate);
+
+        // The file in SVCTRL_CONFIG overrides the variables of each key
+        vars.insert("SVCTRL_SVDIR".to_string(), "/srv/sv".to_string());
+        vars.insert(
+            "SVCTRL_CONFIG".to_string(),
+            env_conf.to_string_lossy().into_owned(),
+        );
+
+        let mut c = Config::default();
+        c.load_layers(Vec::new(), None, lookup(&vars)).unwrap();
+
+        assert_eq!(c.svdir, PathBuf::from("/srv/sv"));
+        assert_eq!(c.stop_timeout, 20);
+        assert_eq!(c.source("stop_timeout"), Source::File(env_conf.clone()));
+        assert_eq!(c.path, Some(env_conf.clone()));
+
+        // The file given by the caller overrides SVCTRL_CONFIG
+        let mut c = Config::default();
+        c.load_layers(Vec::new(), Some(cli_conf.clone()), lookup(&vars))
+            .unwrap();
+
+        assert_eq!(c.stop_timeout, 30);
+        assert_eq!(c.logdir, Some(PathBuf::from("1")));
+        assert_eq!(c.source("stop_timeout"), Source::File(cli_conf.clone()));
+
+        // The variables override the configuration files found
+        vars.remove("SVCTRL_CONFIG");
+
+        let mut c = Config::default();
+        c.load_layers(vec![cli_conf], None, lookup(&vars)).unwrap();
+        assert_eq!(c.stop_timeout, 1);
+        assert_eq!(c.source("stop_timeout"), Source::Env);
+
+        // Empty variables are the same as unset ones
+        vars.insert("SVCTRL_CONFIG".to_string(), String::new());
+        vars.insert("SVCTRL_STOP_TIMEOUT".to_string(), String::new());
+
+        let mut c = Config::default();
+        c.load_layers(Vec::new(), None, lookup(&vars)).unwrap();
+        assert_eq!(c.source("stop_timeout"), Source::Default);
+
+        vars.insert("SVCTRL_STOP_ESCALATE".to_string(), "yes".to_string());
+
+        match Layer::from_lookup(lookup(&vars)) {
+            Err(Error::EnvVar(name, _)) => assert_eq!(name, "SVCTRL_STOP_ESCALATE"),
+            _ => panic!("expected Error::EnvVar"),
+        }
+
+        vars.remove("SVCTRL_STOP_ESCALATE");
+        vars.insert("SVCTRL_FLAP_RESTARTS".to_string(), "0".to_string());
+
+        match Layer::from_lookup(lookup(&vars)) {
+            Err(Error::EnvVar(name, _)) => assert_eq!(name, "SVCTRL_FLAP_RESTARTS"),
+            _ => panic!("expected Error::EnvVar"),
+        }
+
+        // A deadline this far ahead would overflow
+        vars.remove("SVCTRL_FLAP_RESTARTS");
+        vars.insert("SVCTRL_STOP_TIMEOUT".to_string(), u64::MAX.to_string());
+
+        match Layer::from_lookup(lookup(&vars)) {
+            Err(Error::EnvVar(name, _)) => assert_eq!(name, "SVCTRL_STOP_TIMEOUT"),
+            _ => panic!("expected Error::EnvVar"),
+        }
+    }
+
+    #[test]
+    fn test_open_invalid() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("config.toml");
+
+        // A flap_window of 0 would count no restart at all
+        for content in [
+            "stop_timeout = 'soon'\n",
+            "stop_timeout = 0\n",
+            "stop_timeout = 86401\n",
+            "flap_window = 0\n",
+        ]
+        .iter()
+        {
+            fs::write(&path, content).unwrap();
+
+            let mut c = Config {
+                path: Some(path.clone()),
+                ..Default::default()
+            };
+
+            match c.load_conf() {
+                Err(Error::FailedToLoadConf(p, _)) => assert_eq!(p, path),
+                _ => panic!("expected Error::FailedToLoadConf for {:?}", content),
+            }
+        }
+    }
+}
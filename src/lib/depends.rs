@@ -0,0 +1,358 @@
+use crate::configuration::Config;
+use crate::errors::Error;
+use crate::servicedir::show_active_services;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Name of the file inside a service directory where its dependencies are declared
+pub const DEPS_FILE: &str = "svctrl.toml";
+
+/// Dependencies a service declares in the svctrl.toml file of its service directory
+///
+/// # Example
+///
+/// ```toml
+/// # The service can't work without postgresql
+/// depends = ["postgresql"]
+/// # The service works better with nscd but doesn't need it
+/// wants = ["nscd"]
+/// ```
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct Deps {
+    /// Services that must be up before this one is started, if one of them fails
+    /// this service is not started
+    #[serde(default)]
+    pub depends: Vec<String>,
+    /// Services that should be up before this one is started, if one of them fails
+    /// or doesn't exist this service is started anyway
+    #[serde(default)]
+    pub wants: Vec<String>,
+}
+
+impl Deps {
+    /// Returns the dependencies declared in the given service directory, a service
+    /// without a svctrl.toml has no dependencies
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - Path of the service directory
+    pub fn load(p: &Path) -> Result<Self, Error> {
+        let path = p.join(DEPS_FILE);
+
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let mut deps_string = String::new();
+
+        match std::fs::File::open(&path) {
+            Ok(mut f) => {
+                if let Err(e) = f.read_to_string(&mut deps_string) {
+                    return Err(Error::LoadDeps(path, e.to_string()));
+                }
+            }
+            Err(e) => return Err(Error::LoadDeps(path, e.to_string())),
+        };
+
+        match toml::from_str(&deps_string) {
+            Ok(d) => Ok(d),
+            Err(e) => Err(Error::LoadDeps(path, e.to_string())),
+        }
+    }
+}
+
+/// Order in which to act on a set of services so that each one comes after what it
+/// needs when starting, or before what it needs when stopping
+#[derive(Debug, Default, Clone)]
+pub struct Order {
+    /// Names of the services in the order they should be acted on
+    pub names: Vec<String>,
+    /// Services that must have been acted on successfully before each service
+    before: HashMap<String, Vec<String>>,
+}
+
+impl Order {
+    /// Returns an Order that keeps the services in the order given and has no
+    /// dependencies between them, used when dependencies are to be ignored
+    pub fn plain<I, S>(names: I) -> Self
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            names: names.map(|n| n.as_ref().to_string()).collect(),
+            before: HashMap::new(),
+        }
+    }
+
+    /// Returns the services that must have been acted on successfully before the
+    /// given service can be
+    pub fn before(&self, name: &str) -> &[String] {
+        match self.before.get(name) {
+            Some(b) => b,
+            None => &[],
+        }
+    }
+
+    /// Returns the first service that had to be acted on before the given service
+    /// but failed, in which case the given service should be skipped
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - String slice holding the name of the service about to be acted on
+    /// * `failed` - Names of the services that failed so far
+    pub fn failed_before(&self, name: &str, failed: &[String]) -> Option<&String> {
+        self.before(name).iter().find(|b| failed.contains(b))
+    }
+}
+
+/// Mark of a service while sorting, a service that is reached again while it is
+/// still being visited is part of a cycle
+#[derive(PartialEq)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+/// Visits a service and everything it depends on or wants, pushing each service to
+/// `out` once all its dependencies are in it
+fn visit(
+    name: &str,
+    deps: &HashMap<String, Deps>,
+    marks: &mut HashMap<String, Mark>,
+    stack: &mut Vec<String>,
+    out: &mut Vec<String>,
+) -> Result<(), Error> {
+    match marks.get(name) {
+        Some(Mark::Done) => return Ok(()),
+        Some(Mark::Visiting) => {
+            // The cycle is everything on the stack since we first reached this service
+            let start = stack.iter().position(|n| n == name).unwrap_or(0);
+            let mut cycle: Vec<String> = stack[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(Error::Cycle(cycle.join(" -> ")));
+        }
+        None => (),
+    }
+
+    marks.insert(name.to_string(), Mark::Visiting);
+    stack.push(name.to_string());
+
+    if let Some(d) = deps.get(name) {
+        for dep in d.depends.iter().chain(d.wants.iter()) {
+            // Only order what is part of the set
+            if deps.contains_key(dep) {
+                visit(dep, deps, marks, stack, out)?;
+            }
+        }
+    }
+
+    stack.pop();
+    marks.insert(name.to_string(), Mark::Done);
+    out.push(name.to_string());
+
+    Ok(())
+}
+
+/// Returns the services of the set sorted so that each one comes after everything
+/// it depends on or wants that is also part of the set
+fn sort<S>(names: &[S], deps: &HashMap<String, Deps>) -> Result<Vec<String>, Error>
+where
+    S: AsRef<str>,
+{
+    let mut marks: HashMap<String, Mark> = HashMap::new();
+    let mut stack: Vec<String> = Vec::new();
+    let mut out: Vec<String> = Vec::new();
+
+    for name in names {
+        visit(name.as_ref(), deps, &mut marks, &mut stack, &mut out)?;
+    }
+
+    Ok(out)
+}
+
+/// Returns the Order to enable or start the given services in, which includes
+/// everything they depend on or want, read from the service directories in svdir
+///
+/// # Remarks
+///
+/// Services that are wanted but don't exist in svdir are left out, services that
+/// are depended on but don't exist are kept so acting on them fails.
+pub fn start_order<S>(c: &Config, names: &[S]) -> Result<Order, Error>
+where
+    S: AsRef<str>,
+{
+    let mut deps: HashMap<String, Deps> = HashMap::new();
+    let mut queue: Vec<String> = names.iter().map(|n| n.as_ref().to_string()).collect();
+
+    while let Some(name) = queue.pop() {
+        if deps.contains_key(&name) {
+            continue;
+        }
+
+        let d = Deps::load(&c.svdir.join(&name))?;
+
+        queue.extend(d.depends.iter().cloned());
+        queue.extend(d.wants.iter().filter(|w| c.svdir.join(w).is_dir()).cloned());
+
+        deps.insert(name, d);
+    }
+
+    let mut order = Order::default();
+
+    for (name, d) in &deps {
+        order.before.insert(name.clone(), d.depends.clone());
+    }
+
+    order.names = sort(names, &deps)?;
+
+    Ok(order)
+}
+
+/// Returns the Order to disable or stop the given services in, which includes every
+/// enabled service that depends on them, read from the service directories in lndir
+pub fn stop_order<S>(c: &Config, names: &[S]) -> Result<Order, Error>
+where
+    S: AsRef<str>,
+{
+    let mut enabled: HashMap<String, Deps> = HashMap::new();
+
+    if let Some(active) = show_active_services(c) {
+        for name in active {
+            let d = Deps::load(&c.lndir.join(&name))?;
+            enabled.insert(name, d);
+        }
+    }
+
+    // Walk from each service to the enabled services that depend on it
+    let mut deps: HashMap<String, Deps> = HashMap::new();
+    let mut queue: Vec<String> = names.iter().map(|n| n.as_ref().to_string()).collect();
+
+    while let Some(name) = queue.pop() {
+        if deps.contains_key(&name) {
+            continue;
+        }
+
+        for (other, d) in &enabled {
+            if d.depends.contains(&name) {
+                queue.push(other.clone());
+            }
+        }
+
+        let d = enabled.get(&name).cloned().unwrap_or_default();
+        deps.insert(name, d);
+    }
+
+    let mut order = Order::default();
+
+    for (name, d) in &deps {
+        for dep in &d.depends {
+            order
+                .before
+                .entry(dep.clone())
+                .or_insert_with(Vec::new)
+                .push(name.clone());
+        }
+    }
+
+    // Stopping goes the opposite way of starting, start from every service in the
+    // set so the order doesn't depend on which one was given first
+    let mut all: Vec<&String> = deps.keys().collect();
+    all.sort();
+
+    order.names = sort(&all, &deps)?;
+    order.names.reverse();
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    /// Creates a service directory in svdir with the given svctrl.toml
+    fn service(c: &Config, name: &str, deps: &str) {
+        let dir = c.svdir.join(name);
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(DEPS_FILE), deps).unwrap();
+    }
+
+    fn setup(tmpdir: &tempfile::TempDir) -> Config {
+        let c = Config {
+            svdir: tmpdir.path().join("src"),
+            lndir: tmpdir.path().join("dst"),
+            ..Default::default()
+        };
+
+        fs::create_dir_all(&c.svdir).unwrap();
+        fs::create_dir_all(&c.lndir).unwrap();
+
+        service(&c, "db", "");
+        service(&c, "cache", "wants = [\"missing\"]");
+        service(&c, "app", "depends = [\"db\"]\nwants = [\"cache\"]");
+        service(&c, "web", "depends = [\"app\"]");
+
+        c
+    }
+
+    #[test]
+    fn test_start_order() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = setup(&tmpdir);
+
+        let order = start_order(&c, &["web"]).unwrap();
+
+        assert_eq!(order.names, vec!["db", "cache", "app", "web"]);
+        assert_eq!(order.before("web"), &["app".to_string()]);
+        assert_eq!(order.before("app"), &["db".to_string()]);
+        assert!(order.before("cache").is_empty());
+
+        let failed = vec!["db".to_string()];
+        assert_eq!(order.failed_before("app", &failed), Some(&failed[0]));
+        assert_eq!(order.failed_before("web", &failed), None);
+    }
+
+    #[test]
+    fn test_stop_order() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = setup(&tmpdir);
+
+        for name in ["db", "cache", "app", "web"].iter() {
+            symlink(c.svdir.join(name), c.lndir.join(name)).unwrap();
+        }
+
+        let order = stop_order(&c, &["db"]).unwrap();
+
+        assert_eq!(order.names, vec!["web", "app", "db"]);
+        assert_eq!(order.before("db"), &["app".to_string()]);
+        assert_eq!(order.before("app"), &["web".to_string()]);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = setup(&tmpdir);
+
+        service(&c, "db", "wants = [\"web\"]");
+
+        match start_order(&c, &["web"]) {
+            Err(Error::Cycle(s)) => assert_eq!(s, "web -> app -> db -> web"),
+            _ => panic!("expected Error::Cycle"),
+        }
+    }
+
+    #[test]
+    fn test_plain() {
+        let order = Order::plain(["b", "a"].iter());
+
+        assert_eq!(order.names, vec!["b", "a"]);
+        assert!(order.before("a").is_empty());
+    }
+}
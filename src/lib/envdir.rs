@@ -0,0 +1,255 @@
+use crate::errors::Error;
+use crate::utils::write_atomic;
+use std::fs;
+use std::path::Path;
+
+/// Returns whether a string is a valid name of a variable in an envdir, which is
+/// a file name chpst doesn't skip and that can be passed to the environment
+pub fn is_valid(key: &str) -> bool {
+    !key.is_empty() && !key.contains('/') && !key.contains('=') && !key.starts_with('.')
+}
+
+/// Parses a KEY=VALUE pair into the name of a variable and its value
+///
+/// # Arguments
+///
+/// * `pair` - String slice holding the pair, the value may be empty
+pub fn parse(pair: &str) -> Result<(String, String), Error> {
+    let mut split = pair.splitn(2, '=');
+
+    match (split.next(), split.next()) {
+        (Some(k), Some(v)) if is_valid(k) => Ok((k.to_string(), v.to_string())),
+        _ => Err(Error::InvalidEnv(pair.to_string())),
+    }
+}
+
+/// Decodes the contents of a file of an envdir like chpst -e does, returns None if
+/// the file removes the variable from the environment
+///
+/// # Remarks
+///
+/// An empty file removes the variable. Otherwise only the first line is used, with
+/// trailing spaces and tabs removed and each NUL replaced by a newline, so a file
+/// holding a single newline sets the variable to an empty string.
+pub fn decode(content: &[u8]) -> Option<String> {
+    if content.is_empty() {
+        return None;
+    }
+
+    let line = content.split(|b| *b == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+
+    Some(line.trim_end_matches(&[' ', '\t'][..]).replace('\0', "\n"))
+}
+
+/// Encodes a value as the contents of a file of an envdir that chpst -e decodes back
+/// to it, see `decode`
+///
+/// # Arguments
+///
+/// * `key` - String slice holding the name of the variable, used by errors
+/// * `value` - String slice holding the value of the variable
+pub fn encode(key: &str, value: &str) -> Result<Vec<u8>, Error> {
+    // chpst would trim it, as it would a NUL that was encoded from a newline
+    if value.ends_with(&[' ', '\t', '\n'][..]) || value.contains('\0') {
+        return Err(Error::EnvValue(key.to_string()));
+    }
+
+    let mut content = value.replace('\n', "\0").into_bytes();
+    content.push(b'\n');
+
+    Ok(content)
+}
+
+/// Returns the variables of an envdir with their values, sorted by name, a value is
+/// None if the file removes the variable, see `decode`
+///
+/// # Arguments
+///
+/// * `dir` - Path of the envdir, which is empty if it doesn't exist
+pub fn list(dir: &Path) -> Result<Vec<(String, Option<String>)>, Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::Read(dir.to_path_buf(), e)),
+    };
+
+    let mut vars: Vec<(String, Option<String>)> = Vec::new();
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let key = match entry.file_name().to_str() {
+            Some(k) if is_valid(k) => k.to_string(),
+            // chpst skips dotfiles, which includes the ones being written by set
+            _ => continue,
+        };
+
+        if !entry.path().is_file() {
+            continue;
+        }
+
+        match fs::read(entry.path()) {
+            Ok(c) => vars.push((key, decode(&c))),
+            Err(e) => return Err(Error::Read(entry.path(), e)),
+        }
+    }
+
+    vars.sort();
+
+    Ok(vars)
+}
+
+/// Returns the value a variable of an envdir is set to, None if it has no file or
+/// the file removes the variable
+///
+/// # Arguments
+///
+/// * `dir` - Path of the envdir
+/// * `key` - String slice holding the name of the variable
+pub fn get(dir: &Path, key: &str) -> Result<Option<String>, Error> {
+    if !is_valid(key) {
+        return Err(Error::InvalidEnv(key.to_string()));
+    }
+
+    let path = dir.join(key);
+
+    match fs::read(&path) {
+        Ok(c) => Ok(decode(&c)),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::Read(path, e)),
+    }
+}
+
+/// Sets a variable of an envdir, creating the envdir if needed, returns whether the
+/// value changed
+///
+/// # Arguments
+///
+/// * `dir` - Path of the envdir
+/// * `key` - String slice holding the name of the variable
+/// * `value` - String slice holding the value of the variable
+pub fn set(dir: &Path, key: &str, value: &str) -> Result<bool, Error> {
+    if !is_valid(key) {
+        return Err(Error::InvalidEnv(key.to_string()));
+    }
+
+    let content = encode(key, value)?;
+    let path = dir.join(key);
+
+    if fs::read(&path).ok().as_ref() == Some(&content) {
+        return Ok(false);
+    }
+
+    if let Err(e) = fs::create_dir_all(dir) {
+        return Err(Error::Write(dir.to_path_buf(), e));
+    }
+
+    write_atomic(&path, &content)?;
+
+    Ok(true)
+}
+
+/// Removes the file of a variable from an envdir, so the service inherits it from
+/// runsv instead, returns whether there was a file
+///
+/// # Arguments
+///
+/// * `dir` - Path of the envdir
+/// * `key` - String slice holding the name of the variable
+pub fn unset(dir: &Path, key: &str) -> Result<bool, Error> {
+    if !is_valid(key) {
+        return Err(Error::InvalidEnv(key.to_string()));
+    }
+
+    let path = dir.join(key);
+
+    match fs::remove_file(&path) {
+        Ok(_) => Ok(true),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(Error::Remove(path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("PATH=/bin:/usr/bin").unwrap(),
+            ("PATH".to_string(), "/bin:/usr/bin".to_string())
+        );
+        assert_eq!(
+            parse("EMPTY=").unwrap(),
+            ("EMPTY".to_string(), String::new())
+        );
+
+        for pair in ["NOVALUE", "=foo", "../foo=bar", ".hidden=1"].iter() {
+            match parse(pair) {
+                Err(Error::InvalidEnv(_)) => (),
+                _ => panic!("expected Error::InvalidEnv for {}", pair),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b""), None);
+        assert_eq!(decode(b"\n"), Some(String::new()));
+        assert_eq!(decode(b"foo \t\nbar\n"), Some("foo".to_string()));
+        assert_eq!(decode(b"a\0b"), Some("a\nb".to_string()));
+    }
+
+    #[test]
+    fn test_encode() {
+        for value in ["", "foo bar", "a\nb"].iter() {
+            assert_eq!(
+                decode(&encode("K", value).unwrap()),
+                Some(value.to_string())
+            );
+        }
+
+        for value in ["foo ", "foo\t", "foo\n", "a\0b"].iter() {
+            match encode("K", value) {
+                Err(Error::EnvValue(k)) => assert_eq!(k, "K"),
+                _ => panic!("expected Error::EnvValue for {:?}", value),
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_get_unset() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path().join("env");
+
+        assert!(list(&dir).unwrap().is_empty());
+
+        assert!(set(&dir, "PORT", "80").unwrap());
+        assert!(!set(&dir, "PORT", "80").unwrap());
+        assert!(set(&dir, "MOTD", "hello\nworld").unwrap());
+        fs::write(dir.join("LANG"), "").unwrap();
+
+        assert_eq!(fs::read(dir.join("MOTD")).unwrap(), b"hello\0world\n");
+        assert_eq!(get(&dir, "PORT").unwrap(), Some("80".to_string()));
+        assert_eq!(get(&dir, "MISSING").unwrap(), None);
+        assert_eq!(
+            list(&dir).unwrap(),
+            vec![
+                ("LANG".to_string(), None),
+                ("MOTD".to_string(), Some("hello\nworld".to_string())),
+                ("PORT".to_string(), Some("80".to_string())),
+            ]
+        );
+
+        assert!(unset(&dir, "PORT").unwrap());
+        assert!(!unset(&dir, "PORT").unwrap());
+        assert!(!dir.join("PORT").exists());
+
+        match set(&dir, "../PORT", "80") {
+            Err(Error::InvalidEnv(_)) => (),
+            _ => panic!("expected Error::InvalidEnv"),
+        }
+    }
+}
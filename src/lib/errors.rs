@@ -9,6 +9,17 @@ pub enum Error {
     Disabled(String), // The Service is already disabled
     #[fail(display = "Service ({}) is not enabled", _0)]
     NotEnabled(String),
+    #[fail(display = "Service ({}) is not running", _0)]
+    NotRunning(String),
+    #[fail(display = "Service ({}) exited after it was sent HUP", _0)]
+    ReloadFailed(String),
+    #[fail(display = "Service ({}) is masked", _0)]
+    Masked(String),
+    #[fail(
+        display = "Service ({}) has {} errors in its service directory, see check-dir",
+        _0, _1
+    )]
+    Invalid(String, usize),
     #[fail(
         display = "Path {:#?} of service '{}' is claimed by another service",
         _0, _1
@@ -52,9 +63,81 @@ pub enum Error {
     #[fail(display = "Could not remove file on {:#?}! Error: {}", _0, _1)]
     Remove(PathBuf, std::io::Error),
 
-    // Used by status
-    #[fail(display = "Could not read mtime of {:#?}! Error: {}", _0, _1)]
-    Modified(PathBuf, std::io::Error),
+    // Used by check
+    #[fail(display = "Could not execute {:#?}! Error: {}", _0, _1)]
+    Exec(PathBuf, std::io::Error),
+    #[fail(display = "Check of service ({}) failed", _0)]
+    CheckFailed(String),
+
+    // Used by depends
+    #[fail(display = "Failed to load dependencies from {:#?}! Error: {}", _0, _1)]
+    LoadDeps(PathBuf, String),
+    #[fail(display = "Dependency cycle between services: {}", _0)]
+    Cycle(String),
+    #[fail(display = "Skipped service ({}) because service ({}) failed", _0, _1)]
+    DepFailed(String, String),
+
+    // Used by log
+    #[fail(display = "Could not find the log directory of service ({})", _0)]
+    NoLogDir(String),
+
+    // Used by tai64
+    #[fail(display = "Invalid TAI64N label ({})", _0)]
+    Tai64Label(String),
+
+    // Used by status when supervise/status is not the 20 bytes runsv writes
+    #[fail(display = "Status file {:#?} has {} bytes, expected {}", _0, _1, _2)]
+    StatusSize(PathBuf, usize, usize),
+
+    // Used by wait_for when the service doesn't reach the condition before the deadline
+    #[fail(
+        display = "Timed out after {}s waiting for service ({}) to {}",
+        _2, _0, _1
+    )]
+    Timeout(String, String, u64),
+
+    // Used by user mode and user-setup
+    #[fail(display = "Could not find the home directory, $HOME is not set")]
+    NoHome,
+    #[fail(display = "User ({}) does not exist", _0)]
+    NoUser(String),
+    #[fail(display = "Invalid user name ({:?})", _0)]
+    InvalidUser(String),
+    #[fail(display = "Path {:#?} already exists", _0)]
+    Exists(PathBuf),
+
+    // Used by template and envdir
+    #[fail(display = "Invalid service name ({})", _0)]
+    InvalidService(String),
+    #[fail(display = "Invalid environment variable ({}), expected KEY=VALUE", _0)]
+    InvalidEnv(String),
+    #[fail(
+        display = "Value of ({}) can't end with whitespace or hold NUL, chpst would change it",
+        _0
+    )]
+    EnvValue(String),
+    #[fail(display = "Variable ({}) is not set", _0)]
+    NotSet(String),
+
+    // Used by procfs
+    #[fail(display = "Could not parse {:#?}", _0)]
+    ProcStat(PathBuf),
+
+    // Used by runlevel
+    #[fail(display = "Runlevel ({}) does not exist", _0)]
+    NoRunlevel(String),
+    #[fail(display = "Invalid runlevel name ({})", _0)]
+    InvalidRunlevel(String),
+    #[fail(display = "Could not rename {:#?} to {:#?}! Error: {}", _0, _1, _2)]
+    Rename(PathBuf, PathBuf, std::io::Error),
+
+    // When a time in the configuration is 0 or longer than configuration::MAX_SECONDS
+    #[fail(display = "Invalid number of seconds ({}), expected 1 to 86400", _0)]
+    InvalidSeconds(String),
+
+    // When an environment variable of a configuration key can't be parsed
+    #[fail(display = "Invalid value of ${}! Error: {}", _0, _1)]
+    EnvVar(String, String),
 
     // When configuration::Config.load_conf is called without configuration::Config.path being
     // set
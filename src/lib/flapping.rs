@@ -0,0 +1,185 @@
+use crate::configuration::Config;
+use crate::errors::Error;
+use crate::service::Status;
+use crate::utils::write_atomic;
+use crate::watch::is_restart;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the file in statedir holding the `History`
+pub const HISTORY: &str = "flapping.json";
+
+/// What was last seen of a service and when it restarted
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Record {
+    /// Pid of the main process when the service was last seen
+    pub pid: u32,
+    /// Whether runsv wanted the service up when it was last seen
+    pub want_up: bool,
+    /// UNIX times in seconds at which the service restarted, oldest first
+    pub restarts: Vec<u64>,
+}
+
+/// Restarts of services seen over several runs of svctrl, kept in statedir
+///
+/// # Remarks
+///
+/// A restart is recorded at the time runsv wrote in supervise/status, so looking at
+/// a service once in a while is enough to see its last restart. Restarts in between
+/// are only seen if the status is read often enough, like by flapping --sample.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct History {
+    /// Record of each service by name
+    pub services: BTreeMap<String, Record>,
+}
+
+/// Returns the path of the history in statedir
+pub fn path(c: &Config) -> PathBuf {
+    c.statedir.join(HISTORY)
+}
+
+/// Returns a time as seconds since the UNIX epoch
+fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+impl History {
+    /// Reads the history from a file, which is empty if the file doesn't exist
+    ///
+    /// # Remarks
+    ///
+    /// A file that can't be parsed, like one cut short when the system crashed, is
+    /// the same as an empty one, as the history is rebuilt by looking at services.
+    pub fn load(p: &Path) -> Result<Self, Error> {
+        match fs::read(p) {
+            Ok(c) => Ok(serde_json::from_slice(&c).unwrap_or_default()),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::Read(p.to_path_buf(), e)),
+        }
+    }
+
+    /// Writes the history to a file, see `utils::write_atomic`
+    pub fn save(&self, p: &Path) -> Result<(), Error> {
+        if let Some(dir) = p.parent() {
+            if let Err(e) = fs::create_dir_all(dir) {
+                return Err(Error::Write(dir.to_path_buf(), e));
+            }
+        }
+
+        write_atomic(p, &serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    /// Records a status of a service, returns whether it shows a restart since the
+    /// service was last seen, see `watch::is_restart`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - String slice holding the name of the service
+    /// * `s` - Status of the service
+    pub fn observe(&mut self, name: &str, s: &Status) -> bool {
+        let record = self.services.entry(name.to_string()).or_default();
+
+        // The first time a service is seen the default record never wanted it up
+        let restarted = is_restart(record.pid, record.want_up, s);
+
+        if restarted {
+            record.restarts.push(unix_secs(s.since()));
+        }
+
+        record.pid = s.pid();
+        record.want_up = s.want_up();
+
+        restarted
+    }
+
+    /// Removes the restarts older than a window of time before now, and the services
+    /// that aren't looked at anymore
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - Names of the services still looked at
+    /// * `window` - Seconds of restarts to keep
+    /// * `now` - Time the window ends at
+    pub fn prune(&mut self, names: &[String], window: u64, now: SystemTime) {
+        let start = unix_secs(now).saturating_sub(window);
+
+        self.services.retain(|n, _| names.contains(n));
+
+        for record in self.services.values_mut() {
+            record.restarts.retain(|t| *t >= start);
+        }
+    }
+
+    /// Returns how many times a service restarted within a window of time before now
+    pub fn restarts(&self, name: &str, window: u64, now: SystemTime) -> usize {
+        let start = unix_secs(now).saturating_sub(window);
+
+        match self.services.get(name) {
+            Some(r) => r.restarts.iter().filter(|t| **t >= start).count(),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use crate::service::State;
+    use std::time::Duration;
+
+    #[test]
+    fn test_observe() {
+        let now = SystemTime::now();
+        let mut h = History::default();
+
+        let first = Status::fake(State::Run, 10, now - Duration::from_secs(300), true, false);
+
+        assert!(!h.observe("foo", &first));
+        assert!(!h.observe("foo", &first));
+
+        for i in 1..=3 {
+            let since = now - Duration::from_secs(40 - i * 10);
+            assert!(h.observe(
+                "foo",
+                &Status::fake(State::Run, 10 + i as u32, since, true, false)
+            ));
+        }
+
+        assert_eq!(h.restarts("foo", 60, now), 3);
+        assert_eq!(h.restarts("foo", 15, now), 1);
+        assert_eq!(h.restarts("bar", 60, now), 0);
+
+        h.prune(&["foo".to_string()], 15, now);
+        assert_eq!(h.services["foo"].restarts.len(), 1);
+
+        h.prune(&[], 60, now);
+        assert!(h.services.is_empty());
+    }
+
+    #[test]
+    fn test_load_save() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let p = tmpdir.path().join("state").join(HISTORY);
+
+        assert!(History::load(&p).unwrap().services.is_empty());
+
+        let mut h = History::default();
+        h.observe(
+            "foo",
+            &Status::fake(State::Run, 10, SystemTime::now(), true, false),
+        );
+        h.save(&p).unwrap();
+
+        let loaded = History::load(&p).unwrap();
+        assert_eq!(loaded.services["foo"].pid, 10);
+        assert!(loaded.services["foo"].want_up);
+
+        fs::write(&p, "{\"services\":").unwrap();
+        assert!(History::load(&p).unwrap().services.is_empty());
+    }
+}
@@ -1,8 +1,21 @@
 pub mod configuration;
+pub mod depends;
+pub mod envdir;
 pub mod errors;
+pub mod flapping;
+pub mod lint;
+pub mod log;
+pub mod metrics;
+pub mod procfs;
+pub mod runlevel;
 pub mod service;
 pub mod servicedir;
+pub mod sockets;
+pub mod tai64;
+pub mod template;
+pub mod user;
 pub mod utils;
+pub mod watch;
 
 pub use crate::configuration::*;
 pub use crate::errors::*;
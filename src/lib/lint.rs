@@ -0,0 +1,312 @@
+use serde::Serialize;
+use std::fmt::Result as fmtResult;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Scripts of a service directory that runsv executes
+pub const SCRIPTS: &[&str] = &["run", "finish", "check", "log/run"];
+
+/// How bad a Lint is
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The service will probably work but not as expected
+    Warning,
+    /// runsv can't run the service
+    Error,
+}
+
+/// `fmt::Display` for Severity
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Problem found in a file of a service directory
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lint {
+    /// Path of the file relative to the service directory
+    pub file: String,
+    /// How bad the problem is
+    pub severity: Severity,
+    /// What the problem is
+    pub message: String,
+}
+
+/// `fmt::Display` for Lint, like 'error: run: is not executable'
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        write!(f, "{}: {}: {}", self.severity, self.file, self.message)
+    }
+}
+
+impl Lint {
+    fn new(file: &str, severity: Severity, message: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            severity,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Returns the last command of a shell script, joining lines continued with a
+/// backslash and skipping empty lines and comments
+pub fn last_command(script: &str) -> Option<String> {
+    let mut commands: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for line in script.lines() {
+        let line = line.trim();
+
+        if current.is_empty() && (line.is_empty() || line.starts_with('#')) {
+            continue;
+        }
+
+        if let Some(l) = line.strip_suffix('\\') {
+            current.push_str(l);
+            current.push(' ');
+            continue;
+        }
+
+        current.push_str(line);
+        commands.push(current.trim().to_string());
+        current = String::new();
+    }
+
+    if !current.trim().is_empty() {
+        commands.push(current.trim().to_string());
+    }
+
+    commands.pop()
+}
+
+/// Returns the problems of a script that runs a long lived process, which should
+/// be replaced by it with exec so runsv supervises it instead of the shell
+fn lint_exec(file: &str, script: &str, lints: &mut Vec<Lint>) {
+    for line in script.lines().map(|l| l.trim()) {
+        if !line.starts_with('#') && line.ends_with('&') && !line.ends_with("&&") {
+            lints.push(Lint::new(
+                file,
+                Severity::Warning,
+                &format!(
+                    "'{}' runs in the background where runsv can't supervise it",
+                    line
+                ),
+            ));
+        }
+    }
+
+    let last = match last_command(script) {
+        Some(l) => l,
+        None => {
+            lints.push(Lint::new(file, Severity::Error, "has no commands"));
+            return;
+        }
+    };
+
+    // The end of a compound command, what runs inside of it can't be told
+    if ["fi", "done", "esac", "}", ")"].contains(&last.as_str()) {
+        return;
+    }
+
+    if last != "exec" && !last.starts_with("exec ") && !last.starts_with("exec\t") {
+        lints.push(Lint::new(
+            file,
+            Severity::Warning,
+            "last command doesn't use exec, runsv will supervise the shell instead of it",
+        ));
+    }
+}
+
+/// Returns the problems of a script of a service directory
+///
+/// # Arguments
+///
+/// * `dir` - Path of the service directory
+/// * `file` - Path of the script relative to the service directory
+fn lint_script(dir: &Path, file: &str, lints: &mut Vec<Lint>) {
+    let path = dir.join(file);
+
+    let meta = match fs::metadata(&path) {
+        Ok(m) => m,
+        Err(_) => {
+            if file == "run" || file == "log/run" {
+                lints.push(Lint::new(file, Severity::Error, "is missing"));
+            }
+            return;
+        }
+    };
+
+    if !meta.is_file() {
+        lints.push(Lint::new(file, Severity::Error, "is not a regular file"));
+        return;
+    }
+
+    if meta.permissions().mode() & 0o111 == 0 {
+        lints.push(Lint::new(file, Severity::Error, "is not executable"));
+    }
+
+    let content = match fs::read(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            lints.push(Lint::new(
+                file,
+                Severity::Error,
+                &format!("can't be read: {}", e),
+            ));
+            return;
+        }
+    };
+
+    // Compiled programs are executed directly
+    if content.starts_with(b"\x7fELF") {
+        return;
+    }
+
+    if !content.starts_with(b"#!") {
+        lints.push(Lint::new(
+            file,
+            Severity::Error,
+            "has no shebang, runsv can't execute it",
+        ));
+        return;
+    }
+
+    let script = String::from_utf8_lossy(&content);
+    let shebang = script.lines().next().unwrap_or_default();
+
+    if let Some(interpreter) = shebang[2..].split_whitespace().next() {
+        if interpreter.starts_with('/') && !Path::new(interpreter).is_file() {
+            lints.push(Lint::new(
+                file,
+                Severity::Error,
+                &format!("interpreter {} doesn't exist", interpreter),
+            ));
+        }
+    }
+
+    // Only shell scripts can be checked for exec
+    let shell = shebang.ends_with("sh") || shebang.contains("sh ");
+
+    if shell && (file == "run" || file == "log/run") {
+        lint_exec(file, &script, lints);
+    }
+}
+
+/// Returns the problems found in a service directory, which is expected to have an
+/// executable run and, if it has a log directory, an executable log/run. The finish
+/// and check scripts are checked when they exist.
+///
+/// # Arguments
+///
+/// * `dir` - Path of the service directory
+pub fn check_dir(dir: &Path) -> Vec<Lint> {
+    let mut lints: Vec<Lint> = Vec::new();
+
+    if !dir.is_dir() {
+        lints.push(Lint::new(".", Severity::Error, "is not a directory"));
+        return lints;
+    }
+
+    for file in SCRIPTS {
+        if file.starts_with("log/") && !dir.join("log").is_dir() {
+            continue;
+        }
+
+        lint_script(dir, file, &mut lints);
+    }
+
+    lints
+}
+
+/// Returns whether any of the problems keeps runsv from running the service
+pub fn has_errors(lints: &[Lint]) -> bool {
+    lints.iter().any(|l| l.severity == Severity::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    fn script(dir: &Path, file: &str, content: &str, mode: u32) {
+        let path = dir.join(file);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn test_last_command() {
+        assert_eq!(
+            last_command("#!/bin/sh\nexec 2>&1\n\nexec foo \\\n  -f\n# done\n"),
+            Some("exec foo  -f".to_string())
+        );
+        assert_eq!(last_command("#!/bin/sh\n# nothing\n"), None);
+    }
+
+    #[test]
+    fn test_check_dir_ok() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+
+        script(dir, "run", "#!/bin/sh\nexec 2>&1\nexec sleep 1000\n", 0o755);
+        script(dir, "log/run", "#!/bin/sh\nexec svlogd -tt .\n", 0o755);
+        script(dir, "finish", "#!/bin/sh\nrm -f /run/foo.pid\n", 0o755);
+
+        assert!(check_dir(dir).is_empty());
+    }
+
+    #[test]
+    fn test_check_dir_problems() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+
+        script(dir, "run", "#!/bin/sh\nfoo -d &\nfoo\n", 0o644);
+        script(dir, "check", "curl localhost\n", 0o755);
+        fs::create_dir(dir.join("log")).unwrap();
+
+        let lints = check_dir(dir);
+        let found: Vec<(&str, Severity)> = lints
+            .iter()
+            .map(|l| (l.file.as_str(), l.severity))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("run", Severity::Error),
+                ("run", Severity::Warning),
+                ("run", Severity::Warning),
+                ("check", Severity::Error),
+                ("log/run", Severity::Error),
+            ]
+        );
+        assert!(has_errors(&lints));
+        assert_eq!(lints[0].to_string(), "error: run: is not executable");
+    }
+
+    #[test]
+    fn test_check_dir_interpreter() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+
+        script(dir, "run", "#!/nonexistent/sh\nexec foo\n", 0o755);
+
+        let lints = check_dir(dir);
+        assert_eq!(lints.len(), 1);
+        assert_eq!(
+            lints[0].message,
+            "interpreter /nonexistent/sh doesn't exist"
+        );
+    }
+}
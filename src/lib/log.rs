@@ -0,0 +1,366 @@
+use crate::configuration::Config;
+use crate::errors::Error;
+use crate::service::Service;
+use crate::tai64::{format_utc, Tai64N, LABEL_SIZE};
+use crate::utils::read_file;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Returns the directory svlogd writes to according to the given log/run script, the
+/// path is relative to the log directory of the service if it isn't absolute
+///
+/// # Arguments
+///
+/// * `run` - String slice holding the contents of log/run
+///
+/// # Example
+///
+/// ```ignore
+/// let dir = parse_run("#!/bin/sh\nexec svlogd -tt /var/log/sshd\n");
+/// assert_eq!(dir, Some(PathBuf::from("/var/log/sshd")));
+/// ```
+pub fn parse_run(run: &str) -> Option<PathBuf> {
+    // The last line running svlogd is the one that is exec'd
+    let line = run
+        .lines()
+        .rev()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .find(|l| l.split_whitespace().any(is_svlogd))?;
+
+    let mut args = line
+        .split_whitespace()
+        .skip_while(|a| !is_svlogd(a))
+        .skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg {
+            // Options that take their value in the next argument
+            "-r" | "-R" | "-l" | "-b" => {
+                args.next();
+            }
+            a if a.starts_with('-') => (),
+            a => return Some(PathBuf::from(a)),
+        }
+    }
+
+    None
+}
+
+/// Returns whether an argument of log/run is the svlogd program
+fn is_svlogd(a: &str) -> bool {
+    a == "svlogd" || a.ends_with("/svlogd")
+}
+
+/// Returns the directory svlogd of a service writes to, it is taken from the log/run
+/// script of the service or else from logdir in the configuration
+///
+/// # Arguments
+///
+/// * `c` - Config holding logdir
+/// * `sv` - Service to find the log directory of
+pub fn find_logdir(c: &Config, sv: &Service) -> Result<PathBuf, Error> {
+    let logf: PathBuf = sv.make_path("log");
+
+    if let Ok(run) = read_file(&logf.join("run")) {
+        if let Some(dir) = parse_run(&run) {
+            let dir = logf.join(dir);
+
+            if dir.is_dir() {
+                return Ok(dir);
+            }
+        }
+    }
+
+    if let Some(logdir) = &c.logdir {
+        let dir = logdir.join(&sv.name);
+
+        if dir.is_dir() {
+            return Ok(dir);
+        }
+    }
+
+    Err(Error::NoLogDir(sv.name.clone()))
+}
+
+/// Returns the files of a svlogd log directory from oldest to newest, which are
+/// the rotated @*.s and @*.u files followed by current
+///
+/// # Arguments
+///
+/// * `dir` - Path of the log directory
+pub fn log_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) => return Err(Error::Read(dir.to_path_buf(), e)),
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| match p.file_name().and_then(|n| n.to_str()) {
+            Some(n) => n.starts_with('@') && (n.ends_with(".s") || n.ends_with(".u")),
+            None => false,
+        })
+        .collect();
+
+    // The names are TAI64N labels so they sort by time
+    files.sort();
+
+    let current = dir.join("current");
+
+    if current.is_file() {
+        files.push(current);
+    }
+
+    Ok(files)
+}
+
+/// Returns a line of a log with its leading TAI64N label, if any, replaced by the
+/// human readable time it stands for
+pub fn decode_line(line: &str) -> String {
+    if line.len() > LABEL_SIZE && line.is_char_boundary(LABEL_SIZE) {
+        // A label the system clock can't hold is left as it is
+        let time = line[..LABEL_SIZE]
+            .parse::<Tai64N>()
+            .ok()
+            .and_then(|t| t.system_time());
+
+        if let Some(t) = time {
+            return format!("{}{}", format_utc(t), &line[LABEL_SIZE..]);
+        }
+    }
+
+    line.to_string()
+}
+
+/// Follows the current file of a svlogd log directory across rotations, returning
+/// the complete lines written to it since the last time it was polled
+pub struct Follower {
+    /// Path of the current file
+    path: PathBuf,
+    /// Handle of the file being read, which stays valid after svlogd renames it
+    file: File,
+    /// Inode of the file being read to know when current is a new file
+    ino: u64,
+    /// Bytes of the last line read if it didn't end in a newline yet
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    /// Starts following the current file of a log directory from its beginning
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Path of the log directory
+    pub fn new(dir: &Path) -> Result<Self, Error> {
+        let path = dir.join("current");
+        let (file, ino) = Self::open(&path)?;
+
+        Ok(Self {
+            path,
+            file,
+            ino,
+            partial: Vec::new(),
+        })
+    }
+
+    /// Opens a file and returns it with its inode
+    fn open(p: &PathBuf) -> Result<(File, u64), Error> {
+        let file = match File::open(p) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::Open(p.clone(), e)),
+        };
+
+        let ino = match file.metadata() {
+            Ok(m) => m.ino(),
+            Err(e) => return Err(Error::Read(p.clone(), e)),
+        };
+
+        Ok((file, ino))
+    }
+
+    /// Reads what was appended to the file being read into complete lines, each
+    /// decoded only once it is complete so a character svlogd is in the middle of
+    /// writing isn't cut in two
+    fn read_lines(&mut self, lines: &mut Vec<String>) -> Result<(), Error> {
+        if let Err(e) = self.file.read_to_end(&mut self.partial) {
+            return Err(Error::Read(self.path.clone(), e));
+        }
+
+        while let Some(i) = self.partial.iter().position(|b| *b == b'\n') {
+            lines.push(String::from_utf8_lossy(&self.partial[..i]).into_owned());
+            self.partial.drain(..=i);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the lines written since the last poll, if svlogd rotated current in the
+    /// meantime the rest of the old file is read before the new one
+    pub fn poll(&mut self) -> Result<Vec<String>, Error> {
+        let mut lines: Vec<String> = Vec::new();
+
+        self.read_lines(&mut lines)?;
+
+        // current may not exist for a moment while svlogd rotates
+        match std::fs::metadata(&self.path) {
+            Ok(m) if m.ino() != self.ino => {
+                let (file, ino) = Self::open(&self.path)?;
+
+                self.file = file;
+                self.ino = ino;
+                self.read_lines(&mut lines)?;
+            }
+            _ => (),
+        }
+
+        Ok(lines)
+    }
+}
+
+/// Returns the last lines of a log directory, reading the rotated files before
+/// current, and a Follower positioned right after them to keep reading
+///
+/// # Arguments
+///
+/// * `dir` - Path of the log directory
+/// * `n` - Maximum number of lines to return, None to return all of them
+pub fn tail(dir: &Path, n: Option<usize>) -> Result<(Vec<String>, Follower), Error> {
+    let mut lines: VecDeque<String> = VecDeque::new();
+
+    // Open current before reading the rotated files so a rotation in between
+    // is read by the Follower instead of being lost
+    let mut follower = Follower::new(dir)?;
+
+    let mut push = |line: String| {
+        if n == Some(lines.len()) {
+            lines.pop_front();
+        }
+        if n != Some(0) {
+            lines.push_back(line);
+        }
+    };
+
+    for file in log_files(dir)? {
+        // Skip current and what was current when the Follower opened it
+        match std::fs::metadata(&file) {
+            Ok(m) if m.ino() != follower.ino && !file.ends_with("current") => (),
+            _ => continue,
+        }
+
+        let mut buffer: Vec<u8> = Vec::new();
+
+        match File::open(&file) {
+            Ok(mut f) => {
+                if let Err(e) = f.read_to_end(&mut buffer) {
+                    return Err(Error::Read(file, e));
+                }
+            }
+            Err(e) => return Err(Error::Open(file, e)),
+        };
+
+        for line in String::from_utf8_lossy(&buffer).lines() {
+            push(line.to_string());
+        }
+    }
+
+    for line in follower.poll()? {
+        push(line);
+    }
+
+    Ok((lines.into_iter().collect(), follower))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_run() {
+        assert_eq!(
+            parse_run("#!/bin/sh\nexec svlogd -tt /var/log/sshd\n"),
+            Some(PathBuf::from("/var/log/sshd"))
+        );
+        assert_eq!(
+            parse_run("#!/bin/sh\n[ -d main ] || mkdir main\nexec chpst -ulog /usr/bin/svlogd -r _ -t ./main\n"),
+            Some(PathBuf::from("./main"))
+        );
+        assert_eq!(
+            parse_run("#!/bin/sh\n# exec svlogd /tmp\nexec vlogger -t sshd\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_line() {
+        assert_eq!(
+            decode_line("@400000005c7ac8f41d1b6c4c started"),
+            "2019-03-02 18:18:18.488336460 started"
+        );
+        assert_eq!(decode_line("no label here"), "no label here");
+        assert_eq!(decode_line("@notalabel notalabel"), "@notalabel notalabel");
+        assert_eq!(
+            decode_line("@ffffffffffffffff00000000 x"),
+            "@ffffffffffffffff00000000 x"
+        );
+    }
+
+    #[test]
+    fn test_tail() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+
+        fs::write(dir.join("@400000005c7ac8f400000000.s"), "1\n2\n").unwrap();
+        fs::write(dir.join("@400000005c7ac90000000000.u"), "3\n").unwrap();
+        fs::write(dir.join("current"), "4\n5").unwrap();
+        fs::write(dir.join("lock"), "").unwrap();
+
+        let (lines, _) = tail(dir, None).unwrap();
+        assert_eq!(lines, vec!["1", "2", "3", "4"]);
+
+        let (lines, _) = tail(dir, Some(2)).unwrap();
+        assert_eq!(lines, vec!["3", "4"]);
+
+        let (lines, _) = tail(dir, Some(0)).unwrap();
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn test_follower() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+        let current = dir.join("current");
+
+        fs::write(&current, "1\n").unwrap();
+
+        let mut f = Follower::new(dir).unwrap();
+        assert_eq!(f.poll().unwrap(), vec!["1"]);
+        assert!(f.poll().unwrap().is_empty());
+
+        let mut w = fs::OpenOptions::new().append(true).open(&current).unwrap();
+        write!(w, "2\n3").unwrap();
+        assert_eq!(f.poll().unwrap(), vec!["2"]);
+
+        // Rotate like svlogd does
+        writeln!(w).unwrap();
+        fs::rename(&current, dir.join("@400000005c7ac8f400000000.s")).unwrap();
+        fs::write(&current, "4\n").unwrap();
+
+        assert_eq!(f.poll().unwrap(), vec!["3", "4"]);
+
+        // A poll in the middle of a character that is being written
+        let mut w = fs::OpenOptions::new().append(true).open(&current).unwrap();
+        w.write_all(&"é".as_bytes()[..1]).unwrap();
+        assert!(f.poll().unwrap().is_empty());
+
+        w.write_all(&"é\n".as_bytes()[1..]).unwrap();
+        assert_eq!(f.poll().unwrap(), vec!["é"]);
+    }
+}
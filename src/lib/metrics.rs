@@ -0,0 +1,161 @@
+use crate::service::{FullStatus, State};
+
+/// Gauge written for each service, in the text format of Prometheus
+struct Gauge {
+    /// Name of the gauge
+    name: &'static str,
+    /// Description written in the HELP line
+    help: &'static str,
+    /// Returns the value of the gauge for the status of a service, which is None if
+    /// runsv doesn't supervise it, or None if the gauge doesn't apply
+    value: fn(Option<&FullStatus>) -> Option<u64>,
+}
+
+/// Gauges written by `render`, in order
+const GAUGES: [Gauge; 9] = [
+    Gauge {
+        name: "svctrl_service_supervised",
+        help: "Whether runsv supervises the service, the other gauges are left out if not",
+        value: |s| Some(s.is_some() as u64),
+    },
+    Gauge {
+        name: "svctrl_service_up",
+        help: "Whether the service is running its run script, 0 while finish runs",
+        value: |s| s.map(|s| (s.service.state() == State::Run) as u64),
+    },
+    Gauge {
+        name: "svctrl_service_pid",
+        help: "Pid of the main process of the service, 0 if there is none",
+        value: |s| s.map(|s| u64::from(s.service.pid())),
+    },
+    Gauge {
+        name: "svctrl_service_state_seconds",
+        help: "Seconds since the service last changed state",
+        value: |s| s.map(|s| s.service.seconds()),
+    },
+    Gauge {
+        name: "svctrl_service_normally_up",
+        help: "Whether runsv starts the service when it starts, 0 if it has a down file",
+        value: |s| s.map(|s| s.service.normallyup() as u64),
+    },
+    Gauge {
+        name: "svctrl_service_want_up",
+        help: "Whether runsv wants the service up instead of down",
+        value: |s| s.map(|s| s.service.want_up() as u64),
+    },
+    Gauge {
+        name: "svctrl_service_paused",
+        help: "Whether the service was paused with STOP",
+        value: |s| s.map(|s| s.service.paused() as u64),
+    },
+    Gauge {
+        name: "svctrl_service_log_up",
+        help: "Whether the logging subservice is running its run script",
+        value: |s| {
+            s.and_then(|s| s.log.as_ref())
+                .map(|l| (l.state() == State::Run) as u64)
+        },
+    },
+    Gauge {
+        name: "svctrl_service_log_state_seconds",
+        help: "Seconds since the logging subservice last changed state",
+        value: |s| s.and_then(|s| s.log.as_ref()).map(|l| l.seconds()),
+    },
+];
+
+/// Escapes the value of a label, see the text format of Prometheus
+fn escape(v: &str) -> String {
+    v.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Returns the gauges of services in the text format of Prometheus, each gauge
+/// with a 'service' label for each service it applies to
+///
+/// # Arguments
+///
+/// * `services` - Name of each service with its status, None if runsv doesn't
+///   supervise it
+pub fn render(services: &[(String, Option<FullStatus>)]) -> String {
+    let mut out = String::new();
+
+    for g in GAUGES.iter() {
+        out.push_str(&format!("# HELP {} {}\n", g.name, g.help));
+        out.push_str(&format!("# TYPE {} gauge\n", g.name));
+
+        for (name, s) in services {
+            if let Some(v) = (g.value)(s.as_ref()) {
+                out.push_str(&format!(
+                    "{}{{service=\"{}\"}} {}\n",
+                    g.name,
+                    escape(name),
+                    v
+                ));
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::Status;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_render() {
+        let services = vec![
+            (
+                "foo".to_string(),
+                Some(FullStatus {
+                    service: Status::fake(State::Run, 42, UNIX_EPOCH, true, false),
+                    log: Some(Status::fake(State::Run, 43, UNIX_EPOCH, true, false)),
+                    log_error: None,
+                }),
+            ),
+            (
+                "bar".to_string(),
+                Some(FullStatus {
+                    service: Status::fake(State::Down, 0, UNIX_EPOCH, false, false),
+                    log: None,
+                    log_error: None,
+                }),
+            ),
+            (
+                "qux".to_string(),
+                Some(FullStatus {
+                    service: Status::fake(State::Finish, 44, UNIX_EPOCH, true, false),
+                    log: None,
+                    log_error: None,
+                }),
+            ),
+            ("b\"az".to_string(), None),
+        ];
+
+        let out = render(&services);
+
+        for line in [
+            "# TYPE svctrl_service_up gauge",
+            "svctrl_service_supervised{service=\"b\\\"az\"} 0",
+            "svctrl_service_up{service=\"foo\"} 1",
+            "svctrl_service_up{service=\"bar\"} 0",
+            // finish has a pid but the service isn't up
+            "svctrl_service_up{service=\"qux\"} 0",
+            "svctrl_service_pid{service=\"qux\"} 44",
+            "svctrl_service_pid{service=\"foo\"} 42",
+            "svctrl_service_want_up{service=\"bar\"} 0",
+            "svctrl_service_log_up{service=\"foo\"} 1",
+        ]
+        .iter()
+        {
+            assert!(out.lines().any(|l| l == *line), "missing {}", line);
+        }
+
+        // Gauges that don't apply are left out
+        assert!(!out.contains("svctrl_service_up{service=\"b\\\"az\"}"));
+        assert!(!out.contains("svctrl_service_log_up{service=\"bar\"}"));
+    }
+}
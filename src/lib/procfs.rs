@@ -0,0 +1,301 @@
+use crate::errors::Error;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the kernel mounts the proc filesystem
+pub const PROC: &str = "/proc";
+
+/// Clock ticks per second of the times in /proc/PID/stat, which the kernel keeps at
+/// 100 for userspace on every architecture but alpha
+pub const USER_HZ: f64 = 100.0;
+
+/// Process as described by /proc/PID
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Process {
+    /// Pid of the process
+    pub pid: u32,
+    /// Pid of the parent of the process
+    pub ppid: u32,
+    /// Name of the executable, cut to 15 bytes by the kernel
+    pub comm: String,
+    /// Resident memory in bytes
+    pub rss: u64,
+    /// Seconds spent on the CPU in user and kernel mode
+    pub cpu: f64,
+    /// Number of threads
+    pub threads: u32,
+    /// Number of open file descriptors, None if /proc/PID/fd can't be read
+    pub fds: Option<usize>,
+    /// Arguments of the process joined by spaces with control characters replaced by
+    /// spaces, the comm in brackets for kernel threads and zombies that have none
+    pub cmdline: String,
+}
+
+/// Fields of /proc/PID/stat used by `Process`
+#[derive(Debug, PartialEq)]
+pub struct Stat {
+    /// Name of the executable
+    pub comm: String,
+    /// Pid of the parent
+    pub ppid: u32,
+    /// Clock ticks spent in user and kernel mode
+    pub ticks: u64,
+    /// Number of threads
+    pub threads: u32,
+}
+
+/// Parses the contents of /proc/PID/stat
+///
+/// # Remarks
+///
+/// The comm is in parentheses and can hold spaces and parentheses itself, so the
+/// fields after it are found from the last ')'. Counting from 1 like proc(5) does,
+/// ppid is field 4, utime 14, stime 15 and num_threads 20.
+pub fn parse_stat(s: &str) -> Option<Stat> {
+    let open = s.find('(')?;
+    let close = s.rfind(')')?;
+
+    let comm = s.get(open + 1..close)?.to_string();
+    // Starting with the state, field 3
+    let fields: Vec<&str> = s.get(close + 1..)?.split_whitespace().collect();
+
+    let field = |n: usize| fields.get(n - 3).and_then(|f| f.parse::<u64>().ok());
+
+    Some(Stat {
+        comm,
+        ppid: field(4)? as u32,
+        ticks: field(14)? + field(15)?,
+        threads: field(20)? as u32,
+    })
+}
+
+/// Returns the resident memory in bytes from the contents of /proc/PID/status, 0 for
+/// kernel threads that have no VmRSS line
+pub fn parse_rss(s: &str) -> u64 {
+    s.lines()
+        .find(|l| l.starts_with("VmRSS:"))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+        .unwrap_or(0)
+}
+
+/// Reads a process from a proc filesystem
+///
+/// # Arguments
+///
+/// * `root` - Path where proc is mounted, see `PROC`
+/// * `pid` - Pid of the process
+pub fn read(root: &Path, pid: u32) -> Result<Process, Error> {
+    let dir = root.join(pid.to_string());
+    let stat_path = dir.join("stat");
+
+    let stat = match fs::read_to_string(&stat_path) {
+        Ok(s) => s,
+        Err(e) => return Err(Error::Read(stat_path, e)),
+    };
+
+    let stat = match parse_stat(&stat) {
+        Some(s) => s,
+        None => return Err(Error::ProcStat(stat_path)),
+    };
+
+    let rss = fs::read_to_string(dir.join("status"))
+        .map(|s| parse_rss(&s))
+        .unwrap_or(0);
+
+    let fds = fs::read_dir(dir.join("fd")).ok().map(|d| d.count());
+
+    let args: Vec<String> = fs::read(dir.join("cmdline"))
+        .unwrap_or_default()
+        .split(|b| *b == 0)
+        .filter(|a| !a.is_empty())
+        .map(|a| String::from_utf8_lossy(a).replace(|c: char| c.is_control(), " "))
+        .collect();
+
+    let cmdline = if args.is_empty() {
+        format!("[{}]", stat.comm)
+    } else {
+        args.join(" ")
+    };
+
+    Ok(Process {
+        pid,
+        ppid: stat.ppid,
+        comm: stat.comm,
+        rss,
+        cpu: stat.ticks as f64 / USER_HZ,
+        threads: stat.threads,
+        fds,
+        cmdline,
+    })
+}
+
+/// Returns the pid of every process of a proc filesystem with the pid of its parent,
+/// sorted by pid, reading nothing but /proc/PID/stat
+///
+/// # Remarks
+///
+/// Processes that exit while they are read are skipped.
+pub fn parents(root: &Path) -> Result<Vec<(u32, u32)>, Error> {
+    let entries = match fs::read_dir(root) {
+        Ok(e) => e,
+        Err(e) => return Err(Error::Read(PathBuf::from(root), e)),
+    };
+
+    let mut parents: Vec<(u32, u32)> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str().and_then(|n| n.parse::<u32>().ok()))
+        .filter_map(|pid| {
+            let stat = fs::read_to_string(root.join(pid.to_string()).join("stat")).ok()?;
+            Some((pid, parse_stat(&stat)?.ppid))
+        })
+        .collect();
+
+    parents.sort_unstable();
+
+    Ok(parents)
+}
+
+/// Returns the pid of a process and of its descendants with their depth below it,
+/// each process followed by its children sorted by pid, empty if the process isn't
+/// in the list
+///
+/// # Arguments
+///
+/// * `parents` - Pid of each process with the pid of its parent, see `parents`
+/// * `pid` - Pid of the process at the top of the tree
+pub fn tree(parents: &[(u32, u32)], pid: u32) -> Vec<(usize, u32)> {
+    let mut found: Vec<(usize, u32)> = Vec::new();
+    let mut stack: Vec<(usize, u32)> = vec![(0, pid)];
+
+    while let Some((depth, pid)) = stack.pop() {
+        if !parents.iter().any(|(p, _)| *p == pid) {
+            continue;
+        }
+
+        found.push((depth, pid));
+
+        // Pushed in reverse so they are popped by increasing pid
+        let mut children: Vec<u32> = parents
+            .iter()
+            .filter(|(c, ppid)| *ppid == pid && *c != pid)
+            .map(|(c, _)| *c)
+            .collect();
+        children.sort_unstable_by(|a, b| b.cmp(a));
+
+        stack.extend(children.into_iter().map(|c| (depth + 1, c)));
+    }
+
+    found
+}
+
+/// Reads a process and its descendants with their depth below it, see `tree`,
+/// processes that exit while they are read are skipped
+///
+/// # Arguments
+///
+/// * `root` - Path where proc is mounted, see `PROC`
+/// * `parents` - Pid of each process with the pid of its parent, see `parents`
+/// * `pid` - Pid of the process at the top of the tree
+pub fn read_tree(root: &Path, parents: &[(u32, u32)], pid: u32) -> Vec<(usize, Process)> {
+    tree(parents, pid)
+        .into_iter()
+        .filter_map(|(depth, pid)| read(root, pid).ok().map(|p| (depth, p)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    fn fake(root: &Path, pid: u32, ppid: u32, comm: &str, cmdline: &[u8]) {
+        let dir = root.join(pid.to_string());
+        fs::create_dir_all(dir.join("fd")).unwrap();
+        fs::write(
+            dir.join("stat"),
+            format!(
+                "{} ({}) S {} 1 1 0 -1 4194560 100 0 0 0 150 50 0 0 20 0 3 0 100 1000 10",
+                pid, comm, ppid
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("status"), "Name:\tx\nVmRSS:\t    2048 kB\n").unwrap();
+        fs::write(dir.join("cmdline"), cmdline).unwrap();
+        fs::write(dir.join("fd").join("0"), "").unwrap();
+    }
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = parse_stat("42 (a) (b c)) R 7 42 42 0 -1 0 0 0 0 0 12 3 0 0 20 0 5 0").unwrap();
+
+        assert_eq!(
+            stat,
+            Stat {
+                comm: "a) (b c)".to_string(),
+                ppid: 7,
+                ticks: 15,
+                threads: 5,
+            }
+        );
+        assert_eq!(parse_stat("42 (cut"), None);
+    }
+
+    #[test]
+    fn test_parse_rss() {
+        assert_eq!(parse_rss("Name:\tfoo\nVmRSS:\t  1234 kB\n"), 1234 * 1024);
+        assert_eq!(parse_rss("Name:\tkthreadd\n"), 0);
+    }
+
+    #[test]
+    fn test_read_tree() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = tmpdir.path();
+
+        fake(root, 10, 1, "runsv", b"runsv\0foo\0");
+        fake(root, 11, 10, "foo", b"/usr/bin/foo\0-f\0");
+        fake(root, 13, 11, "worker", b"");
+        fake(root, 12, 11, "worker", b"foo:\tworker\0");
+        fake(root, 20, 1, "bar", b"bar\0");
+        fs::create_dir(root.join("self")).unwrap();
+
+        let p = read(root, 11).unwrap();
+        assert_eq!(p.ppid, 10);
+        assert_eq!(p.rss, 2048 * 1024);
+        assert!((p.cpu - 2.0).abs() < f64::EPSILON);
+        assert_eq!(p.threads, 3);
+        assert_eq!(p.fds, Some(1));
+        assert_eq!(p.cmdline, "/usr/bin/foo -f");
+
+        // Only stat is needed to find the tree
+        fs::create_dir(root.join("30")).unwrap();
+        fs::write(
+            root.join("30/stat"),
+            "30 (baz) S 1 1 1 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0",
+        )
+        .unwrap();
+
+        let parents = parents(root).unwrap();
+        assert_eq!(
+            parents,
+            vec![(10, 1), (11, 10), (12, 11), (13, 11), (20, 1), (30, 1)]
+        );
+
+        let t: Vec<(usize, u32, String)> = read_tree(root, &parents, 11)
+            .into_iter()
+            .map(|(d, p)| (d, p.pid, p.cmdline))
+            .collect();
+        assert_eq!(
+            t,
+            vec![
+                (0, 11, "/usr/bin/foo -f".to_string()),
+                (1, 12, "foo: worker".to_string()),
+                (1, 13, "[worker]".to_string())
+            ]
+        );
+        assert!(tree(&parents, 99).is_empty());
+    }
+}
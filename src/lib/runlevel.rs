@@ -0,0 +1,232 @@
+use crate::configuration::Config;
+use crate::errors::Error;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+
+/// Name of the symlink in runsvdir that points at the runlevel in use
+pub const CURRENT: &str = "current";
+
+/// Name of the symlink in runsvdir that points at the runlevel used before the last switch
+pub const PREVIOUS: &str = "previous";
+
+/// Name of the temporary symlink used while switching runlevels
+const CURRENT_NEW: &str = "current.new";
+
+/// Returns whether a string is a valid name for a runlevel, which is a single path
+/// component that isn't one of the symlinks kept by runsvchdir
+pub fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains('/')
+        && ![".", "..", CURRENT, PREVIOUS, CURRENT_NEW].contains(&name)
+}
+
+/// Returns the names of the runlevels in runsvdir, sorted
+///
+/// # Arguments
+///
+/// * `c` - Config holding runsvdir
+pub fn list(c: &Config) -> Result<Vec<String>, Error> {
+    let entries = match fs::read_dir(&c.runsvdir) {
+        Ok(e) => e,
+        Err(e) => return Err(Error::Read(c.runsvdir.clone(), e)),
+    };
+
+    let mut levels: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        // Only real directories, current and previous are symlinks to them
+        .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter_map(|e| e.file_name().to_str().map(|n| n.to_string()))
+        .filter(|n| is_valid(n))
+        .collect();
+
+    levels.sort();
+
+    Ok(levels)
+}
+
+/// Returns the name of the runlevel that the current symlink in runsvdir points at
+///
+/// # Arguments
+///
+/// * `c` - Config holding runsvdir
+pub fn current(c: &Config) -> Result<String, Error> {
+    let link = c.runsvdir.join(CURRENT);
+
+    let target = match fs::read_link(&link) {
+        Ok(t) => t,
+        Err(e) => return Err(Error::Read(link, e)),
+    };
+
+    match target.file_name().and_then(|n| n.to_str()) {
+        Some(n) => Ok(n.to_string()),
+        None => Err(Error::NoRunlevel(target.display().to_string())),
+    }
+}
+
+/// Returns the path of the directory of a runlevel, which must exist
+///
+/// # Arguments
+///
+/// * `c` - Config holding runsvdir
+/// * `name` - String slice holding the name of the runlevel
+pub fn path(c: &Config, name: &str) -> Result<PathBuf, Error> {
+    if !is_valid(name) {
+        return Err(Error::InvalidRunlevel(name.to_string()));
+    }
+
+    let dir = c.runsvdir.join(name);
+
+    if !dir.is_dir() {
+        return Err(Error::NoRunlevel(name.to_string()));
+    }
+
+    Ok(dir)
+}
+
+/// Creates the directory of a new runlevel, which has no services enabled, and
+/// returns its path
+///
+/// # Arguments
+///
+/// * `c` - Config holding runsvdir
+/// * `name` - String slice holding the name of the runlevel
+pub fn create(c: &Config, name: &str) -> Result<PathBuf, Error> {
+    if !is_valid(name) {
+        return Err(Error::InvalidRunlevel(name.to_string()));
+    }
+
+    let dir = c.runsvdir.join(name);
+
+    if dir.exists() {
+        return Err(Error::Exists(dir));
+    }
+
+    match fs::create_dir_all(&dir) {
+        Ok(_) => Ok(dir),
+        Err(e) => Err(Error::Write(dir, e)),
+    }
+}
+
+/// Switches the runlevel in use like runsvchdir does, runsvdir notices that the current
+/// symlink changed and starts and stops services to match the new runlevel
+///
+/// # Arguments
+///
+/// * `c` - Config holding runsvdir
+/// * `name` - String slice holding the name of the runlevel to switch to
+///
+/// # Remarks
+///
+/// A symlink current.new is created next to current and renamed over it, which is
+/// atomic, after current is kept as previous. If the last rename fails previous is
+/// renamed back to current.
+pub fn switch(c: &Config, name: &str) -> Result<(), Error> {
+    path(c, name)?;
+
+    let current = c.runsvdir.join(CURRENT);
+    let new = c.runsvdir.join(CURRENT_NEW);
+    let previous = c.runsvdir.join(PREVIOUS);
+
+    if let Err(e) = fs::remove_file(&new) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(Error::Remove(new, e));
+        }
+    }
+
+    // Relative like the symlinks runsvchdir creates
+    if let Err(e) = symlink(name, &new) {
+        return Err(Error::Link(PathBuf::from(name), new, e));
+    }
+
+    if let Err(e) = fs::remove_file(&previous) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(Error::Remove(previous, e));
+        }
+    }
+
+    if current.symlink_metadata().is_ok() {
+        if let Err(e) = fs::rename(&current, &previous) {
+            return Err(Error::Rename(current, previous, e));
+        }
+    }
+
+    if let Err(e) = fs::rename(&new, &current) {
+        // Leave runsvdir pointing at what it was using
+        let _ = fs::rename(&previous, &current);
+        return Err(Error::Rename(new, current, e));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    fn setup(tmpdir: &tempfile::TempDir) -> Config {
+        let c = Config {
+            runsvdir: tmpdir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        fs::create_dir(c.runsvdir.join("default")).unwrap();
+        fs::create_dir(c.runsvdir.join("single")).unwrap();
+        symlink("default", c.runsvdir.join(CURRENT)).unwrap();
+
+        c
+    }
+
+    #[test]
+    fn test_list_current() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = setup(&tmpdir);
+
+        assert_eq!(list(&c).unwrap(), vec!["default", "single"]);
+        assert_eq!(current(&c).unwrap(), "default");
+    }
+
+    #[test]
+    fn test_switch() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = setup(&tmpdir);
+
+        switch(&c, "single").unwrap();
+
+        assert_eq!(current(&c).unwrap(), "single");
+        assert_eq!(
+            fs::read_link(c.runsvdir.join(PREVIOUS)).unwrap(),
+            PathBuf::from("default")
+        );
+        assert!(!c.runsvdir.join(CURRENT_NEW).exists());
+
+        match switch(&c, "missing") {
+            Err(Error::NoRunlevel(n)) => assert_eq!(n, "missing"),
+            _ => panic!("expected Error::NoRunlevel"),
+        }
+        assert_eq!(current(&c).unwrap(), "single");
+    }
+
+    #[test]
+    fn test_create() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = setup(&tmpdir);
+
+        assert_eq!(create(&c, "rescue").unwrap(), c.runsvdir.join("rescue"));
+        assert!(path(&c, "rescue").is_ok());
+
+        match create(&c, "default") {
+            Err(Error::Exists(_)) => (),
+            _ => panic!("expected Error::Exists"),
+        }
+
+        for name in ["", "current", "../etc", "previous"].iter() {
+            match create(&c, name) {
+                Err(Error::InvalidRunlevel(_)) => (),
+                _ => panic!("expected Error::InvalidRunlevel for {}", name),
+            }
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use crate::configuration::Config;
 use crate::errors::Error;
-use crate::utils::read_file;
-use crate::utils::write_to_fifo;
+use crate::tai64::{Tai64N, PACKED_SIZE};
+use crate::utils::read_bytes;
+use crate::utils::{deadline, time_left, write_atomic, write_to_fifo};
+use serde::{Serialize, Serializer};
 use std::fmt::Result as fmtResult;
 use std::fmt::{Display, Formatter};
 use std::os::unix::fs::symlink;
 use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Represents a service directory by runit
 pub struct Service {
@@ -20,60 +24,379 @@ pub struct Service {
     config: Config,
 }
 
+/// Size in bytes of the supervise/status file written by runsv
+pub const STATUS_SIZE: usize = 20;
+
+/// Time between each read of supervise/status while waiting for a service
+const WAIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time the compound actions like restart wait for a service when no other time is
+/// given, the same as sv
+pub const DEFAULT_WAIT: Duration = Duration::from_secs(7);
+
+/// Time reload gives a service to handle HUP before checking it is still running
+const RELOAD_GRACE: Duration = Duration::from_millis(500);
+
+/// State of the main process of a service as runsv knows it, stored in the
+/// last byte of supervise/status
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    /// The service is not running
+    Down,
+    /// The ./run script of the service is running
+    Run,
+    /// The ./finish script of the service is running
+    Finish,
+}
+
+/// `fmt::Display` for State, using the same words as runit's sv status
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        match self {
+            State::Down => write!(f, "down"),
+            State::Run => write!(f, "run"),
+            State::Finish => write!(f, "finish"),
+        }
+    }
+}
+
+/// Condition that `Service::wait_for` waits for a service to reach, modelled after
+/// what sv -w checks for each of the control commands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    /// The service is running its ./run script
+    Up,
+    /// The service is down and not running its ./finish script
+    Down,
+    /// The service was started after the given time or is running and won't be restarted
+    Once(SystemTime),
+    /// The runsv instance of the service exited
+    Exit,
+    /// The service changed state after the given time and is running again, or runsv
+    /// doesn't want it up at all
+    Restart(SystemTime),
+    /// The service is not paused
+    Cont,
+    /// The service is paused
+    Pause,
+}
+
+/// `fmt::Display` for Condition, meant to be used after 'waiting for service to'
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        match self {
+            Condition::Up => write!(f, "be up"),
+            Condition::Down => write!(f, "be down"),
+            Condition::Once(_) => write!(f, "run once"),
+            Condition::Exit => write!(f, "exit"),
+            Condition::Restart(_) => write!(f, "restart"),
+            Condition::Cont => write!(f, "continue"),
+            Condition::Pause => write!(f, "pause"),
+        }
+    }
+}
+
+impl Condition {
+    /// Returns the Condition that can be waited for after writing a control character
+    /// to supervise/control, or None if the control character has no visible effect
+    /// in supervise/status
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - String slice holding the control character that was sent
+    /// * `t` - Time right before the control character was sent
+    pub fn from_signal(s: &str, t: SystemTime) -> Option<Self> {
+        match s {
+            "u" => Some(Condition::Up),
+            "d" => Some(Condition::Down),
+            "o" => Some(Condition::Once(t)),
+            "e" | "x" => Some(Condition::Exit),
+            "t" | "k" => Some(Condition::Restart(t)),
+            "c" => Some(Condition::Cont),
+            "p" => Some(Condition::Pause),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the given Status satisfies the condition, `Condition::Exit` is
+    /// never satisfied by a Status as it requires runsv to not be running
+    pub fn reached(&self, s: &Status) -> bool {
+        let up = s.pid != 0;
+
+        match self {
+            Condition::Up => up && s.state == State::Run,
+            Condition::Down => !up && s.state == State::Down,
+            Condition::Once(t) => (!up && s.since > *t) || (up && !s.want_up),
+            Condition::Exit => false,
+            Condition::Restart(t) => (!up && !s.want_up) || (up && s.since > *t && !s.term),
+            Condition::Cont => !(up && s.paused),
+            Condition::Pause => up && s.paused,
+        }
+    }
+}
+
+/// Step of stopping a service that finally brought it down
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopStep {
+    /// The service went down after being sent the down command
+    Down,
+    /// The service went down after being sent TERM again
+    Term,
+    /// The service went down after being sent KILL
+    Kill,
+}
+
+/// `fmt::Display` for StopStep, the name of what was sent to the service
+impl Display for StopStep {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        match self {
+            StopStep::Down => write!(f, "down"),
+            StopStep::Term => write!(f, "TERM"),
+            StopStep::Kill => write!(f, "KILL"),
+        }
+    }
+}
+
 /// Represents the status of a service, used by status
+///
+/// # Remarks
+///
+/// Serializing a Status gives an object with these keys, which are kept stable for
+/// scripts reading the output of status --format json:
+/// - name: string, the name of the service or 'log' for a logging subservice
+/// - state: string, one of 'down', 'run' or 'finish'
+/// - pid: number, pid of the main process, 0 if there is none
+/// - since: number, UNIX time in seconds of the last state change
+/// - seconds: number, seconds since the last state change
+/// - paused: boolean
+/// - want_up: boolean, whether runsv wants the service up instead of down
+/// - term: boolean, whether runsv sent TERM to the service
+/// - normallyup: boolean, false if the service directory has a down file
+#[derive(Serialize)]
 pub struct Status {
     /// Name of the service, which is the directory
     name: String,
-    /// Status of the service either down or run available under supervise/stat
-    /// but supervise/pid is used instead
-    status: String,
-    /// Pid of the main process of the service available under supervise/pid
+    /// State of the service as written by runsv to supervise/status
+    state: State,
+    /// Pid of the main process of the service, 0 if there is none
     pid: u32,
-    /// Time in seconds since the service is either alive or dead, more specifically
-    /// it is the time until the last change to supervise/pid since it changes whenever
-    /// the state of the main service process in the system changes.
+    /// Time at which the service last changed state, taken from the TAI64N
+    /// timestamp in supervise/status
+    #[serde(serialize_with = "serialize_unix_secs")]
+    since: SystemTime,
+    /// Time in seconds since the service last changed state, 0 if the timestamp
+    /// is in the future
+    #[serde(rename = "seconds")]
     talive: u64,
+    /// Whether the service was paused by sending it STOP via the control fifo
+    paused: bool,
+    /// Whether runsv wants the service up ('u') instead of down ('d')
+    want_up: bool,
+    /// Whether runsv sent a TERM to the service and is waiting for it to stop
+    term: bool,
+    /// Whether the service is started when runsv starts, false if the service
+    /// directory has a file called down
+    normallyup: bool,
+}
+
+/// Serializes a time as the number of seconds since the UNIX epoch, times before
+/// it are serialized as 0
+fn serialize_unix_secs<S>(t: &SystemTime, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_u64(t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+}
+
+/// Status of a service together with the status of its logging subservice, which
+/// is None if the service has no log directory or its status couldn't be read
+///
+/// # Remarks
+///
+/// Serializing it gives the object of `Status` with the key 'log' added, holding
+/// the object of the logging subservice or null, and the key 'log_error' holding
+/// why the status of the logging subservice couldn't be read, only if it couldn't.
+#[derive(Serialize)]
+pub struct FullStatus {
+    /// Status of the service itself
+    #[serde(flatten)]
+    pub service: Status,
+    /// Status of the logging subservice, if the service has one
+    pub log: Option<Status>,
+    /// Error reading the status of the logging subservice, which doesn't keep the
+    /// status of the service from being read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_error: Option<String>,
+}
+
+impl FullStatus {
+    /// Returns the status of a service and of its logging subservice
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - A Service type that holds path where the service is linked and its name
+    pub fn new(s: &Service) -> Result<Self, Error> {
+        let mut service = Status::default();
+        service.status(s, false)?;
+
+        let mut log = None;
+        let mut log_error = None;
+
+        if s.has_log() {
+            let mut l = Status::default();
+
+            match l.status(s, true) {
+                Ok(_) => log = Some(l),
+                Err(e) => log_error = Some(e.to_string()),
+            }
+        }
+
+        Ok(Self {
+            service,
+            log,
+            log_error,
+        })
+    }
 }
 
 /// Default implementation of status, it is made manually instead
-/// of using #[derive(Default)] to create a status String with a
-/// sane capacity
+/// of using #[derive(Default)] because neither State nor SystemTime
+/// have a Default implementation that makes sense here
 impl Default for Status {
     fn default() -> Self {
         Self {
             name: String::new(),
-            status: String::with_capacity(4),
+            state: State::Down,
             pid: 0,
+            since: UNIX_EPOCH,
             talive: 0,
+            paused: false,
+            want_up: false,
+            term: false,
+            normallyup: true,
         }
     }
 }
 
+#[cfg(test)]
+impl Status {
+    /// Returns a status decoded from a supervise/status record holding the given
+    /// fields, for the tests of modules that look at statuses written by runsv
+    pub(crate) fn fake(
+        state: State,
+        pid: u32,
+        since: SystemTime,
+        want_up: bool,
+        paused: bool,
+    ) -> Self {
+        let mut b = [0u8; STATUS_SIZE];
+        b[0..12].copy_from_slice(&Tai64N::from(since).to_bytes());
+        b[12..16].copy_from_slice(&pid.to_le_bytes());
+        b[16] = paused as u8;
+        b[17] = if want_up { b'u' } else { b'd' };
+        b[19] = match state {
+            State::Down => 0,
+            State::Run => 1,
+            State::Finish => 2,
+        };
+
+        let mut s = Self::default();
+        s.decode(&b);
+        s
+    }
+}
+
 /// `fmt::Display` for Status, formatted according to the output of runit's sv status
 ///
 /// # Remarks
 ///
 /// This is meant to completely match the output of 'sv status' that is present on
-/// Void Linux.
+/// Void Linux, including the order in which the qualifiers are printed. Like sv it
+/// uses the pid and not the state to decide whether the service is down.
 impl Display for Status {
     fn fmt(&self, f: &mut Formatter) -> fmtResult {
-        if self.status == "down" {
-            write!(
-                f,
-                "{}: {}: {}s, normally up",
-                self.status, self.name, self.talive
-            )
-        } else {
+        let up = self.pid != 0;
+
+        if up {
             write!(
                 f,
                 "{}: {}: (pid {}) {}s",
-                self.status, self.name, self.pid, self.talive
-            )
+                self.state, self.name, self.pid, self.talive
+            )?;
+        } else {
+            write!(f, "down: {}: {}s", self.name, self.talive)?;
+        }
+
+        if up && !self.normallyup {
+            write!(f, ", normally down")?;
+        }
+        if !up && self.normallyup {
+            write!(f, ", normally up")?;
         }
+        if up && self.paused {
+            write!(f, ", paused")?;
+        }
+        if !up && self.want_up {
+            write!(f, ", want up")?;
+        }
+        if up && !self.want_up {
+            write!(f, ", want down")?;
+        }
+        if up && self.term {
+            write!(f, ", got TERM")?;
+        }
+
+        Ok(())
     }
 }
 
 impl Status {
+    /// Returns the name of the service, 'log' for a logging subservice
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the state of the service
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Returns the pid of the main process of the service, 0 if there is none
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Returns the time at which the service last changed state
+    pub fn since(&self) -> SystemTime {
+        self.since
+    }
+
+    /// Returns the seconds since the service last changed state
+    pub fn seconds(&self) -> u64 {
+        self.talive
+    }
+
+    /// Returns whether the service is paused
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns whether runsv wants the service up instead of down
+    pub fn want_up(&self) -> bool {
+        self.want_up
+    }
+
+    /// Returns whether runsv sent TERM to the service
+    pub fn term(&self) -> bool {
+        self.term
+    }
+
+    /// Returns whether the service is started when runsv starts
+    pub fn normallyup(&self) -> bool {
+        self.normallyup
+    }
+
     /// Stores the values of a service into a Status struct
     ///
     /// # Arguments
@@ -84,56 +407,100 @@ impl Status {
     ///
     /// # Remarks
     ///
-    /// This function reads supervised/pid instead of supervise/stat to see if the service
-    /// is active or not. If the supervise/pid is empty then the service is considered to
-    /// be down.
+    /// This function reads the binary supervise/status file that runsv keeps up to date
+    /// instead of guessing from supervise/pid, see `Status::decode` for its layout.
     ///
-    /// .elapsed is used for checking against SystemTime not a monotonic function which
-    /// can yield inconsistent results in the system.
+    /// Like sv the name of a logging subservice is always 'log'.
+    ///
+    /// The time since the last change is checked against SystemTime, not a monotonic
+    /// clock, because that is what runsv writes in the timestamp.
     pub fn status(&mut self, s: &Service, l: bool) -> Result<&mut Self, Error> {
-        let mut pidf: PathBuf = PathBuf::from(&s.dstpath);
+        let mut statf: PathBuf = PathBuf::from(&s.dstpath);
+        let downf: PathBuf;
 
-        if !&pidf.exists() {
+        if !&statf.exists() {
             return Err(Error::Disabled(s.name.clone()));
         }
 
         if l {
-            pidf = Service::make_path(&s, "log/supervise/pid");
+            statf = s.make_path("log/supervise/status");
+            downf = s.make_path("log/down");
         } else {
-            pidf = Service::make_path(&s, "supervise/pid");
-        }
-
-        // Get status of the service
-        match read_file(&pidf) {
-            Ok(b) => {
-                if b.is_empty() {
-                    self.status = "down".to_string();
-                    self.pid = 0
-                } else {
-                    self.pid = b.trim().parse::<u32>()?;
-                    self.status = "run".to_string()
-                }
-            }
-            Err(e) => return Err(e),
-        };
+            statf = s.make_path("supervise/status");
+            downf = s.make_path("down");
+        }
 
-        // Get the modification time of the pid file which can be used
-        // to know the time the process was alive
-        let time = match std::fs::metadata(&pidf) {
-            Ok(t) => t,
-            Err(e) => return Err(Error::Modified(pidf, e)),
-        };
+        let buffer = read_bytes(&statf)?;
 
-        self.talive = match time.modified().unwrap().elapsed() {
-            Ok(t) => t.as_secs(),
-            Err(e) => return Err(Error::SystemTime(e)),
-        };
+        if buffer.len() != STATUS_SIZE {
+            return Err(Error::StatusSize(statf, buffer.len(), STATUS_SIZE));
+        }
+
+        self.decode(&buffer);
+
+        // runsv only checks for the down file when it starts, so this is
+        // what the service will do on the next boot
+        self.normallyup = !downf.exists();
 
         // Get our name from the name of the Service given to us
-        self.name = s.name.clone();
+        if l {
+            self.name = "log".to_string();
+        } else {
+            self.name = s.name.clone();
+        }
 
         Ok(self)
     }
+
+    /// Fills the struct from the 20 bytes record that runsv writes to supervise/status
+    ///
+    /// # Arguments
+    ///
+    /// * `b` - Exactly `STATUS_SIZE` bytes read from a supervise/status file
+    ///
+    /// # Remarks
+    ///
+    /// The layout of the record is:
+    /// - 0..8 TAI64 label of the last state change, big endian
+    /// - 8..12 nanoseconds of the last state change, big endian
+    /// - 12..16 pid of the main process, little endian, 0 if there is none
+    /// - 16 1 if the service is paused
+    /// - 17 'u' if runsv wants the service up, 'd' if it wants it down
+    /// - 18 1 if runsv sent TERM to the service
+    /// - 19 0 for down, 1 for run and 2 for finish
+    ///
+    /// # Panics
+    ///
+    /// Panics if `b` is shorter than `STATUS_SIZE`.
+    pub fn decode(&mut self, b: &[u8]) -> &mut Self {
+        let mut tai: [u8; PACKED_SIZE] = [0; PACKED_SIZE];
+        let mut pid: [u8; 4] = [0; 4];
+
+        tai.copy_from_slice(&b[0..12]);
+        pid.copy_from_slice(&b[12..16]);
+
+        // A label out of range is the same as no timestamp at all
+        self.since = Tai64N::from_bytes(tai)
+            .and_then(|t| t.system_time())
+            .unwrap_or(UNIX_EPOCH);
+        self.talive = match SystemTime::now().duration_since(self.since) {
+            Ok(t) => t.as_secs(),
+            // runsv's clock was ahead of ours, sv reports 0 in this case
+            Err(_) => 0,
+        };
+
+        self.pid = u32::from_le_bytes(pid);
+        self.paused = b[16] != 0;
+        self.want_up = b[17] == b'u';
+        self.term = b[18] != 0;
+        self.state = match b[19] {
+            1 => State::Run,
+            2 => State::Finish,
+            _ => State::Down,
+        };
+
+        self
+    }
 }
 
 impl Service {
@@ -169,6 +536,16 @@ impl Service {
         self.dstpath.join("log").is_dir()
     }
 
+    /// Returns the path of env/ in the service directory, which run usually gives to
+    /// chpst -e, see `envdir`
+    pub fn envdir(&self) -> Result<PathBuf, Error> {
+        if !self.srcpath.is_dir() {
+            return Err(Error::NotExist(self.name.clone(), self.srcpath.clone()));
+        }
+
+        Ok(self.srcpath.join("env"))
+    }
+
     /// Returns the struct given with srcpath and dstpath filled in
     ///
     /// # Remarks
@@ -249,45 +626,364 @@ impl Service {
         p
     }
 
-    /// Try to stop a service by sending a down signal to runsv
-    pub fn stop(&self) -> Result<(), Error> {
+    /// Try to stop a service by sending a down signal to runsv, waiting and escalating
+    /// as set by stop_timeout and stop_escalate in the configuration
+    pub fn stop(&self) -> Result<StopStep, Error> {
+        self.stop_with(
+            Duration::from_secs(self.config.stop_timeout),
+            self.config.stop_escalate,
+        )
+    }
+
+    /// Try to stop a service by sending a down signal to runsv, returns the step that
+    /// brought the service down
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait for the service to go down after each step
+    /// * `escalate` - Whether to send TERM and then KILL if the service doesn't go down
+    ///   in time, if false a timeout is returned right after the first step
+    ///
+    /// # Remarks
+    ///
+    /// Each step waits up to `t` on its own, so with `escalate` stopping a service
+    /// that never goes down takes three times `t` before the timeout is returned.
+    pub fn stop_with(&self, t: Duration, escalate: bool) -> Result<StopStep, Error> {
         let target: PathBuf = PathBuf::from(&self.dstpath);
 
         if !target.exists() {
             return Err(Error::NotEnabled(self.name.clone()));
         }
 
-        self.signal("d")?;
-
-        let mut enabled: bool = true;
+        // Steps after which a timeout moves on to the next one
+        let tried: &[(StopStep, &str)] = if escalate {
+            &[(StopStep::Down, "d"), (StopStep::Term, "t")]
+        } else {
+            &[]
+        };
 
-        // Try 5 times in a loop to read the supervise/stat file
-        for _ in 1..5 {
-            let buffer = read_file(&Self::make_path(&self, "supervise/stat"))?;
+        for (step, signal) in tried {
+            self.signal(signal)?;
 
-            if buffer == "down\n" {
-                enabled = false;
-                break;
+            match self.wait_for(Condition::Down, t) {
+                Ok(_) => return Ok(*step),
+                Err(Error::Timeout(..)) => (),
+                Err(e) => return Err(e),
             }
+        }
 
-            // Wait for 0.5 second
-            std::thread::sleep(std::time::Duration::from_millis(500));
+        let (last, signal) = if escalate {
+            (StopStep::Kill, "k")
+        } else {
+            (StopStep::Down, "d")
+        };
+
+        self.signal(signal)?;
+        self.wait_for(Condition::Down, t)?;
+
+        Ok(last)
+    }
+
+    /// Returns bool indicating whether the main process of the service is running
+    pub fn is_running(&self) -> Result<bool, Error> {
+        Ok(Status::default().status(self, false)?.pid != 0)
+    }
+
+    /// Restart a service by sending it TERM, CONT and up and waiting for runsv to
+    /// start it again
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait for the service to be restarted
+    pub fn restart(&self, t: Duration) -> Result<(), Error> {
+        let since = SystemTime::now();
+
+        self.signal("tcu")?;
+        self.wait_for(Condition::Restart(since), t)
+    }
+
+    /// Restart a service like `Service::restart` but only if it is running, returns
+    /// whether the service was restarted
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait for the service to be restarted
+    pub fn try_restart(&self, t: Duration) -> Result<bool, Error> {
+        if !self.is_running()? {
+            return Ok(false);
+        }
+
+        let since = SystemTime::now();
+
+        self.signal("tc")?;
+        self.wait_for(Condition::Restart(since), t)?;
+
+        Ok(true)
+    }
+
+    /// Reload a service by sending it HUP and confirm it is still running afterwards
+    ///
+    /// # Remarks
+    ///
+    /// The service is given `RELOAD_GRACE` to handle HUP, after which it must still
+    /// run with the same pid, a new pid means it exited and runsv started it again.
+    pub fn reload(&self) -> Result<(), Error> {
+        let pid = Status::default().status(self, false)?.pid;
+
+        if pid == 0 {
+            return Err(Error::NotRunning(self.name.clone()));
         }
 
-        if enabled {
-            return Err(Error::CouldNotDisable(self.name.clone()));
+        self.signal("h")?;
+        std::thread::sleep(RELOAD_GRACE);
+
+        if Status::default().status(self, false)?.pid != pid {
+            return Err(Error::ReloadFailed(self.name.clone()));
         }
 
-        // If we reached here we
-        // 1. Wrote to the fifo
-        // 2. Read the stat file
-        // 3. Confirmed the service is down
         Ok(())
     }
 
+    /// Bring a service down and send it TERM and then KILL if it doesn't go down in
+    /// time regardless of the configuration, returns the step that brought it down
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait for each step
+    pub fn force_stop(&self, t: Duration) -> Result<StopStep, Error> {
+        self.stop_with(t, true)
+    }
+
+    /// Restart a service by sending it TERM and CONT and send it KILL if it doesn't
+    /// restart in time, returns whether KILL had to be sent
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait before and after sending KILL
+    pub fn force_reload(&self, t: Duration) -> Result<bool, Error> {
+        let since = SystemTime::now();
+
+        self.signal("tc")?;
+
+        match self.wait_for(Condition::Restart(since), t) {
+            Ok(_) => return Ok(false),
+            Err(Error::Timeout(..)) => (),
+            Err(e) => return Err(e),
+        }
+
+        self.signal("k")?;
+        self.wait_for(Condition::Restart(since), t)?;
+
+        Ok(true)
+    }
+
+    /// Returns whether something is in the active services directory under the name
+    /// of the service, like the symlink of a service that is enabled, which
+    /// `Service::enable` never replaces
+    pub fn is_linked(&self) -> bool {
+        self.dstpath.symlink_metadata().is_ok()
+    }
+
+    /// Returns bool indicating whether the service directory has a down file, which
+    /// keeps runsv from starting the service when it starts
+    pub fn is_normally_down(&self) -> bool {
+        self.srcpath.join("down").exists()
+    }
+
+    /// Makes the service start or not start when runsv starts by removing or creating
+    /// the down file in its service directory, returns false if it already did
+    ///
+    /// # Arguments
+    ///
+    /// * `up` - Whether the service should be started when runsv starts
+    ///
+    /// # Remarks
+    ///
+    /// runsv only looks at the down file when it starts, so this doesn't change the
+    /// state of a running service.
+    pub fn set_normally_up(&self, up: bool) -> Result<bool, Error> {
+        if !self.srcpath.is_dir() {
+            return Err(Error::NotExist(self.name.clone(), self.srcpath.clone()));
+        }
+
+        let downf = self.srcpath.join("down");
+
+        if up != self.is_normally_down() {
+            return Ok(false);
+        }
+
+        if up {
+            return match std::fs::remove_file(&downf) {
+                Ok(_) => Ok(true),
+                Err(e) => Err(Error::Remove(downf, e)),
+            };
+        }
+
+        write_atomic(&downf, b"")?;
+
+        Ok(true)
+    }
+
+    /// Returns bool indicating whether the service has a ./check script which tells
+    /// whether the service is really usable once it is up
+    pub fn has_check(&self) -> bool {
+        self.srcpath.join("check").is_file()
+    }
+
+    /// Runs the ./check script of the service once from the service directory, returns
+    /// whether it exited with 0
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time the script can run for before it is killed
+    pub fn run_check(&self, t: Duration) -> Result<bool, Error> {
+        let checkf: PathBuf = self.srcpath.join("check");
+        let deadline = deadline(t);
+
+        let mut child = match Command::new(&checkf)
+            .current_dir(&self.srcpath)
+            .stdin(Stdio::null())
+            .spawn()
+        {
+            Ok(c) => c,
+            Err(e) => return Err(Error::Exec(checkf, e)),
+        };
+
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status.success());
+            }
+
+            let left = time_left(deadline);
+
+            if left.is_zero() {
+                // Don't leave the script behind, it is of no use anymore
+                child.kill()?;
+                child.wait()?;
+                return Err(Error::Timeout(
+                    self.name.clone(),
+                    "pass ./check".to_string(),
+                    t.as_secs(),
+                ));
+            }
+
+            std::thread::sleep(std::cmp::min(WAIT_INTERVAL, left));
+        }
+    }
+
+    /// Runs the ./check script of the service until it succeeds, if the service has
+    /// no ./check script it is considered ready
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait for the script to succeed
+    pub fn wait_ready(&self, t: Duration) -> Result<(), Error> {
+        if !self.has_check() {
+            return Ok(());
+        }
+
+        let deadline = deadline(t);
+
+        loop {
+            match self.run_check(time_left(deadline)) {
+                Ok(true) => return Ok(()),
+                Ok(false) => (),
+                Err(Error::Timeout(..)) => return Err(Error::CheckFailed(self.name.clone())),
+                Err(e) => return Err(e),
+            }
+
+            let left = time_left(deadline);
+
+            if left.is_zero() {
+                return Err(Error::CheckFailed(self.name.clone()));
+            }
+
+            std::thread::sleep(std::cmp::min(WAIT_INTERVAL, left));
+        }
+    }
+
+    /// Waits for the service to be in the state runsv wants it in, if that is up it
+    /// also waits for the ./check script to succeed, like sv check
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait for the service
+    pub fn check(&self, t: Duration) -> Result<(), Error> {
+        let deadline = deadline(t);
+
+        if !Status::default().status(self, false)?.want_up {
+            return self.wait_for(Condition::Down, t);
+        }
+
+        self.wait_for(Condition::Up, t)?;
+        self.wait_ready(time_left(deadline))
+    }
+
+    /// Returns bool indicating whether a runsv instance is supervising the service,
+    /// which is the case if supervise/ok is a fifo with a reader on the other end
+    pub fn is_supervised(&self) -> bool {
+        unix_named_pipe::open_write(self.make_path("supervise/ok")).is_ok()
+    }
+
+    /// Polls supervise/status of a service until it reaches the given Condition
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - Condition that the service must reach
+    /// * `t` - Maximum amount of time to wait for, the status is always checked at least once
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Bring the service up and give it 7 seconds to start, like sv -w 7 up
+    /// sv.signal("u")?;
+    /// sv.wait_for(Condition::Up, Duration::from_secs(7))?;
+    /// ```
+    pub fn wait_for(&self, c: Condition, t: Duration) -> Result<(), Error> {
+        let deadline = deadline(t);
+
+        loop {
+            let reached = match c {
+                Condition::Exit => !self.is_supervised(),
+                _ => c.reached(Status::default().status(self, false)?),
+            };
+
+            if reached {
+                return Ok(());
+            }
+
+            let left = time_left(deadline);
+
+            if left.is_zero() {
+                return Err(Error::Timeout(
+                    self.name.clone(),
+                    c.to_string(),
+                    t.as_secs(),
+                ));
+            }
+
+            std::thread::sleep(std::cmp::min(WAIT_INTERVAL, left));
+        }
+    }
+
     /// Disable a service by trying to stop it and if successful remove it from the
     /// active service directory by removing a symlink.
-    pub fn disable(&self) -> Result<(), Error> {
+    ///
+    /// Returns the step that brought the service down, see `Service::stop`.
+    pub fn disable(&self) -> Result<StopStep, Error> {
+        self.disable_with(
+            Duration::from_secs(self.config.stop_timeout),
+            self.config.stop_escalate,
+        )
+    }
+
+    /// Disable a service like `Service::disable`, stopping it like `Service::stop_with`
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait for the service to go down after each step
+    /// * `escalate` - Whether to send TERM and then KILL if the service doesn't go down
+    pub fn disable_with(&self, t: Duration, escalate: bool) -> Result<StopStep, Error> {
         let target: PathBuf = PathBuf::from(&self.dstpath);
 
         if !target.exists() {
@@ -300,7 +996,34 @@ impl Service {
             return Err(Error::CantDisable(self.name.clone()));
         }
 
-        Self::stop(&self)?;
+        let step = match self.stop_with(t, escalate) {
+            Ok(s) => s,
+            Err(Error::Timeout(..)) => return Err(Error::CouldNotDisable(self.name.clone())),
+            Err(e) => return Err(e),
+        };
+
+        self.unlink()?;
+
+        Ok(step)
+    }
+
+    /// Removes the symlink of a service from the active service directory without
+    /// stopping it, used for runlevels that are not in use where the service may be
+    /// supervised by the runlevel that is
+    pub fn unlink(&self) -> Result<(), Error> {
+        let target: PathBuf = PathBuf::from(&self.dstpath);
+
+        if target == self.srcpath {
+            return Err(Error::CantDisable(self.name.clone()));
+        }
+
+        // Only remove symlinks, never a service definition
+        match target.symlink_metadata() {
+            Ok(ref m) if m.is_dir() => return Err(Error::IsDir(target)),
+            Ok(ref m) if m.is_file() => return Err(Error::IsFile(target)),
+            Ok(_) => (),
+            Err(_) => return Err(Error::Disabled(self.name.clone())),
+        }
 
         match std::fs::remove_file(&target) {
             Ok(_) => Ok(()),
@@ -308,6 +1031,56 @@ impl Service {
         }
     }
 
+    /// Returns bool indicating whether the service is masked, which is when maskdir has a
+    /// file named after it
+    pub fn is_masked(&self) -> bool {
+        self.config
+            .maskdir
+            .join(&self.name)
+            .symlink_metadata()
+            .is_ok()
+    }
+
+    /// Masks the service so it can't be enabled or brought up, returns false if it
+    /// already was
+    ///
+    /// # Remarks
+    ///
+    /// The service doesn't need to exist, so it can be masked before the package that
+    /// ships it is installed. Masking doesn't stop or disable the service.
+    pub fn mask(&self) -> Result<bool, Error> {
+        if self.is_masked() {
+            return Ok(false);
+        }
+
+        let maskdir = &self.config.maskdir;
+
+        if let Err(e) = std::fs::create_dir_all(maskdir) {
+            return Err(Error::Write(maskdir.clone(), e));
+        }
+
+        let maskf = maskdir.join(&self.name);
+
+        match std::fs::write(&maskf, "") {
+            Ok(_) => Ok(true),
+            Err(e) => Err(Error::Write(maskf, e)),
+        }
+    }
+
+    /// Unmasks the service, returns false if it wasn't masked
+    pub fn unmask(&self) -> Result<bool, Error> {
+        if !self.is_masked() {
+            return Ok(false);
+        }
+
+        let maskf = self.config.maskdir.join(&self.name);
+
+        match std::fs::remove_file(&maskf) {
+            Ok(_) => Ok(true),
+            Err(e) => Err(Error::Remove(maskf, e)),
+        }
+    }
+
     /// Writes a string to a fifo of a service
     ///
     /// # Arguments
@@ -319,6 +1092,8 @@ impl Service {
     /// It does not check if the signal was consumed successfully by runsv only if the
     /// write was successfull
     ///
+    /// Signals that start the service, 'u' and 'o', are refused if the service is masked.
+    ///
     /// # Example
     /// ```
     /// let conf = configuration::Config {
@@ -349,6 +1124,10 @@ impl Service {
             return Err(Error::NotEnabled(self.name.clone()));
         }
 
+        if s.as_ref().contains(&['u', 'o'][..]) && self.is_masked() {
+            return Err(Error::Masked(self.name.clone()));
+        }
+
         match write_to_fifo(Self::make_path(&self, "supervise/control"), s.as_ref()) {
             Ok(_) => Ok(()),
             Err(e) => Err(e),
@@ -365,6 +1144,10 @@ impl Service {
             return Err(Error::NotExist(self.name.clone(), source));
         }
 
+        if self.is_masked() {
+            return Err(Error::Masked(self.name.clone()));
+        }
+
         // Check if service is already enabled (is a symlink)
         if let Ok(v) = std::fs::symlink_metadata(&target) {
             // Our target can't exist as a directory
@@ -416,9 +1199,9 @@ mod tests {
         fs::create_dir_all(&tmpdir.path().join("dst")).unwrap();
 
         let test_conf = Config {
-            path: None,
             svdir: tmpdir.path().join("src"),
             lndir: tmpdir.path().join("dst"),
+            ..Default::default()
         };
 
         let test_service = Service {
@@ -462,4 +1245,424 @@ mod tests {
             assert_eq!(&t.dstpath, &t.config.lndir.join(&n));
         }
     }
+
+    /// Builds a supervise/status record the same way runsv's update_status does
+    fn record(secs: u64, nanos: u32, pid: u32, flags: [u8; 4]) -> Vec<u8> {
+        let mut b = Vec::with_capacity(STATUS_SIZE);
+        b.extend_from_slice(&Tai64N::from(UNIX_EPOCH + Duration::new(secs, nanos)).to_bytes());
+        b.extend_from_slice(&pid.to_le_bytes());
+        b.extend_from_slice(&flags);
+        b
+    }
+
+    #[test]
+    fn test_decode_run() {
+        let mut s = Status::default();
+        let b = record(1_500_000_000, 42, 1234, [0, b'u', 0, 1]);
+
+        s.decode(&b);
+
+        assert_eq!(s.state, State::Run);
+        assert_eq!(s.pid, 1234);
+        assert_eq!(s.since, UNIX_EPOCH + Duration::new(1_500_000_000, 42));
+        assert!(s.want_up);
+        assert!(!s.paused);
+        assert!(!s.term);
+        assert!(s.talive > 0);
+    }
+
+    #[test]
+    fn test_decode_flags() {
+        let mut s = Status::default();
+        let b = record(1_500_000_000, 0, 0x0102_0304, [1, b'd', 1, 2]);
+
+        s.decode(&b);
+
+        assert_eq!(s.state, State::Finish);
+        assert_eq!(s.pid, 0x0102_0304);
+        assert!(!s.want_up);
+        assert!(s.paused);
+        assert!(s.term);
+    }
+
+    #[test]
+    fn test_decode_future() {
+        let mut s = Status::default();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let b = record(now.as_secs() + 3600, 0, 0, [0, b'd', 0, 0]);
+
+        s.decode(&b);
+
+        assert_eq!(s.state, State::Down);
+        assert_eq!(s.talive, 0);
+    }
+
+    #[test]
+    fn test_decode_corrupt_label() {
+        let mut s = Status::default();
+        let mut b = record(1_500_000_000, 0, 1234, [0, b'u', 0, 1]);
+        b[0..12].copy_from_slice(&[0xff; 12]);
+
+        s.decode(&b);
+
+        assert_eq!(s.since, UNIX_EPOCH);
+        assert_eq!(s.pid, 1234);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut s = Status {
+            name: "test".to_string(),
+            talive: 10,
+            want_up: true,
+            ..Default::default()
+        };
+
+        assert_eq!(s.to_string(), "down: test: 10s, normally up, want up");
+
+        s.want_up = false;
+        s.normallyup = false;
+        assert_eq!(s.to_string(), "down: test: 10s");
+
+        s.state = State::Run;
+        s.pid = 123;
+        assert_eq!(
+            s.to_string(),
+            "run: test: (pid 123) 10s, normally down, want down"
+        );
+
+        s.want_up = true;
+        s.normallyup = true;
+        s.paused = true;
+        s.term = true;
+        assert_eq!(s.to_string(), "run: test: (pid 123) 10s, paused, got TERM");
+
+        s.state = State::Finish;
+        s.paused = false;
+        s.term = false;
+        assert_eq!(s.to_string(), "finish: test: (pid 123) 10s");
+    }
+
+    #[test]
+    fn test_serialize() {
+        let t = setup();
+        let b = record(1_500_000_000, 0, 1234, [0, b'u', 0, 1]);
+
+        fs::create_dir_all(t.make_path("supervise")).unwrap();
+        fs::write(t.make_path("supervise/status"), &b).unwrap();
+
+        let s = FullStatus::new(&t).unwrap();
+        let v = serde_json::to_value(&s).unwrap();
+
+        assert_eq!(v["name"], "test");
+        assert_eq!(v["state"], "run");
+        assert_eq!(v["pid"], 1234);
+        assert_eq!(v["since"], 1_500_000_000);
+        assert_eq!(v["want_up"], true);
+        assert_eq!(v["normallyup"], true);
+        assert!(v["seconds"].is_u64());
+        assert!(v["log"].is_null());
+
+        fs::create_dir_all(t.make_path("log")).unwrap();
+
+        // The log directory exists but runsv never wrote the status of the logger
+        let s = FullStatus::new(&t).unwrap();
+        assert_eq!(s.service.pid, 1234);
+        assert!(s.log.is_none());
+        assert!(s.log_error.is_some());
+
+        fs::create_dir_all(t.make_path("log/supervise")).unwrap();
+        fs::write(t.make_path("log/supervise/status"), &b).unwrap();
+
+        let v = serde_json::to_value(FullStatus::new(&t).unwrap()).unwrap();
+        assert_eq!(v["log"]["name"], "log");
+        assert_eq!(v["log"]["state"], "run");
+        assert!(v.get("log_error").is_none());
+    }
+
+    #[test]
+    fn test_status_normallyup() {
+        let t = setup();
+        let b = record(1_500_000_000, 0, 1234, [0, b'u', 0, 1]);
+
+        fs::create_dir_all(t.make_path("log/supervise")).unwrap();
+        fs::write(t.make_path("log/supervise/status"), &b).unwrap();
+        fs::write(t.make_path("log/down"), b"").unwrap();
+
+        let mut s = Status::default();
+        s.status(&t, true).unwrap();
+
+        assert_eq!(s.name, "log");
+        assert!(!s.normallyup);
+    }
+
+    #[test]
+    fn test_condition_reached() {
+        let t = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let mut s = Status {
+            state: State::Run,
+            pid: 1234,
+            since: t + Duration::from_secs(1),
+            want_up: true,
+            ..Default::default()
+        };
+
+        assert!(Condition::Up.reached(&s));
+        assert!(!Condition::Down.reached(&s));
+        assert!(Condition::Restart(t).reached(&s));
+        assert!(!Condition::Restart(s.since).reached(&s));
+        assert!(!Condition::Once(t).reached(&s));
+        assert!(Condition::Cont.reached(&s));
+        assert!(!Condition::Exit.reached(&s));
+
+        s.paused = true;
+        assert!(Condition::Pause.reached(&s));
+        assert!(!Condition::Cont.reached(&s));
+
+        s.state = State::Finish;
+        assert!(!Condition::Up.reached(&s));
+        assert!(!Condition::Down.reached(&s));
+
+        s.state = State::Down;
+        s.pid = 0;
+        s.want_up = false;
+        assert!(Condition::Down.reached(&s));
+        assert!(Condition::Once(t).reached(&s));
+        assert!(Condition::Restart(s.since).reached(&s));
+    }
+
+    #[test]
+    fn test_wait_for() {
+        let t = setup();
+        let b = record(1_500_000_000, 0, 0, [0, b'd', 0, 0]);
+
+        fs::create_dir_all(t.make_path("supervise")).unwrap();
+        fs::write(t.make_path("supervise/status"), &b).unwrap();
+
+        assert!(t.wait_for(Condition::Down, Duration::from_secs(0)).is_ok());
+        assert!(t.wait_for(Condition::Exit, Duration::from_secs(0)).is_ok());
+
+        match t.wait_for(Condition::Up, Duration::from_millis(300)) {
+            Err(Error::Timeout(n, c, 0)) => {
+                assert_eq!(n, "test");
+                assert_eq!(c, "be up");
+            }
+            _ => panic!("expected Error::Timeout"),
+        }
+    }
+
+    #[test]
+    fn test_try_restart_down() {
+        let t = setup();
+        let b = record(1_500_000_000, 0, 0, [0, b'd', 0, 0]);
+
+        fs::create_dir_all(t.make_path("supervise")).unwrap();
+        fs::write(t.make_path("supervise/status"), &b).unwrap();
+
+        // There is no control fifo so this would fail if it tried to signal
+        assert!(!t.try_restart(DEFAULT_WAIT).unwrap());
+
+        match t.reload() {
+            Err(Error::NotRunning(n)) => assert_eq!(n, "test"),
+            _ => panic!("expected Error::NotRunning"),
+        }
+    }
+
+    /// Writes an executable ./check script with the given body to the service
+    fn write_check(t: &Service, body: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let checkf = t.srcpath.join("check");
+
+        fs::create_dir_all(&t.srcpath).unwrap();
+        fs::write(&checkf, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&checkf, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_run_check() {
+        let t = setup();
+
+        assert!(!t.has_check());
+        assert!(t.wait_ready(Duration::from_secs(0)).is_ok());
+
+        write_check(&t, "exit 0");
+        assert!(t.has_check());
+        assert!(t.run_check(DEFAULT_WAIT).unwrap());
+
+        write_check(&t, "exit 1");
+        assert!(!t.run_check(DEFAULT_WAIT).unwrap());
+
+        match t.wait_ready(Duration::from_millis(300)) {
+            Err(Error::CheckFailed(n)) => assert_eq!(n, "test"),
+            _ => panic!("expected Error::CheckFailed"),
+        }
+
+        write_check(&t, "exec sleep 5");
+        match t.run_check(Duration::from_millis(300)) {
+            Err(Error::Timeout(n, _, 0)) => assert_eq!(n, "test"),
+            _ => panic!("expected Error::Timeout"),
+        }
+    }
+
+    /// Creates supervise/control as a fifo and returns its reading end so signals
+    /// can be sent without runsv
+    fn control_fifo(t: &Service) -> fs::File {
+        let fifo = t.make_path("supervise/control");
+
+        fs::create_dir_all(t.make_path("supervise")).unwrap();
+        unix_named_pipe::create(&fifo, None).unwrap();
+        unix_named_pipe::open_read(&fifo).unwrap()
+    }
+
+    #[test]
+    fn test_reload() {
+        let t = setup();
+        let _control = control_fifo(&t);
+        let status = t.make_path("supervise/status");
+
+        fs::write(&status, record(1_500_000_000, 0, 10, [0, b'u', 0, 1])).unwrap();
+        t.reload().unwrap();
+
+        // The service exits on HUP and runsv starts it again
+        let path = status.clone();
+        let runsv = std::thread::spawn(move || {
+            std::thread::sleep(RELOAD_GRACE / 5);
+            fs::write(&path, record(1_500_000_001, 0, 11, [0, b'u', 0, 1])).unwrap();
+        });
+
+        match t.reload() {
+            Err(Error::ReloadFailed(n)) => assert_eq!(n, "test"),
+            _ => panic!("expected Error::ReloadFailed"),
+        }
+        runsv.join().unwrap();
+    }
+
+    #[test]
+    fn test_set_normally_up() {
+        let t = setup();
+
+        fs::create_dir_all(&t.srcpath).unwrap();
+
+        assert!(!t.is_normally_down());
+        assert!(t.set_normally_up(false).unwrap());
+        assert!(t.is_normally_down());
+        assert!(!t.set_normally_up(false).unwrap());
+        assert!(!t.srcpath.join(".down.svctrl").exists());
+
+        assert!(t.set_normally_up(true).unwrap());
+        assert!(!t.is_normally_down());
+        assert!(!t.set_normally_up(true).unwrap());
+    }
+
+    #[test]
+    fn test_mask() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let mut t = setup();
+
+        t.config.maskdir = tmpdir.path().join("masked");
+        fs::create_dir_all(t.config.svdir.join("foo")).unwrap();
+        fs::create_dir_all(&t.config.lndir).unwrap();
+        t.rename("foo".to_string()).unwrap();
+
+        assert!(t.mask().unwrap());
+        assert!(!t.mask().unwrap());
+        assert!(t.is_masked());
+
+        match t.enable() {
+            Err(Error::Masked(n)) => assert_eq!(n, "foo"),
+            _ => panic!("expected Error::Masked"),
+        }
+
+        symlink(&t.srcpath, &t.dstpath).unwrap();
+
+        match t.signal("u") {
+            Err(Error::Masked(_)) => (),
+            _ => panic!("expected Error::Masked"),
+        }
+
+        assert!(t.unmask().unwrap());
+        assert!(!t.unmask().unwrap());
+        assert!(!t.is_masked());
+    }
+
+    #[test]
+    fn test_unlink() {
+        let mut t = setup();
+
+        fs::create_dir_all(t.config.svdir.join("foo")).unwrap();
+        fs::create_dir_all(&t.config.lndir).unwrap();
+        t.rename("foo".to_string()).unwrap();
+
+        t.enable().unwrap();
+        t.unlink().unwrap();
+
+        assert!(t.dstpath.symlink_metadata().is_err());
+        assert!(t.srcpath.is_dir());
+
+        match t.unlink() {
+            Err(Error::Disabled(_)) => (),
+            _ => panic!("expected Error::Disabled"),
+        }
+
+        fs::create_dir(&t.dstpath).unwrap();
+
+        match t.unlink() {
+            Err(Error::IsDir(_)) => (),
+            _ => panic!("expected Error::IsDir"),
+        }
+    }
+
+    #[test]
+    fn test_stop_with() {
+        use std::io::Read;
+
+        let t = setup();
+        let mut control = control_fifo(&t);
+        let mut sent = String::new();
+
+        fs::write(
+            t.make_path("supervise/status"),
+            record(1_500_000_000, 0, 0, [0, b'd', 0, 0]),
+        )
+        .unwrap();
+        assert_eq!(
+            t.stop_with(Duration::from_secs(0), true).unwrap(),
+            StopStep::Down
+        );
+
+        // A service that never goes down
+        fs::write(
+            t.make_path("supervise/status"),
+            record(1_500_000_000, 0, 1234, [0, b'd', 1, 1]),
+        )
+        .unwrap();
+
+        match t.stop_with(Duration::from_secs(0), false) {
+            Err(Error::Timeout(..)) => (),
+            _ => panic!("expected Error::Timeout"),
+        }
+
+        match t.stop_with(Duration::from_secs(0), true) {
+            Err(Error::Timeout(..)) => (),
+            _ => panic!("expected Error::Timeout"),
+        }
+
+        control.read_to_string(&mut sent).unwrap();
+        assert_eq!(sent, "dddtk");
+    }
+
+    #[test]
+    fn test_status_size() {
+        let t = setup();
+        let statf = t.make_path("supervise/status");
+
+        fs::create_dir_all(t.make_path("supervise")).unwrap();
+        fs::write(&statf, b"run\n").unwrap();
+
+        match Status::default().status(&t, false) {
+            Err(Error::StatusSize(p, 4, STATUS_SIZE)) => assert_eq!(p, statf),
+            _ => panic!("expected Error::StatusSize"),
+        }
+    }
 }
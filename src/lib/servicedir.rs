@@ -1,6 +1,10 @@
 use crate::configuration::Config;
+use crate::errors::Error;
+use serde::Serialize;
+use std::fmt::Result as fmtResult;
+use std::fmt::{Display, Formatter};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::vec::Vec;
 
 /*
@@ -51,3 +55,194 @@ pub fn show_all_services(c: &Config) -> Option<Vec<String>> {
         None => None,
     }
 }
+
+/// Returns either a vector of strings representing the name of the directories in the active
+/// services directory that have a down file, which runsv doesn't start when it starts, or
+/// None if there are no services active
+pub fn show_normally_down(c: &Config) -> Option<Vec<String>> {
+    show_dirs(&c.lndir).map(|e| {
+        e.into_iter()
+            .filter(|s| c.lndir.join(s).join("down").exists())
+            .collect()
+    })
+}
+
+/// Kind of entry in the active services directory that isn't a service runsvdir
+/// can supervise as svctrl manages it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Problem {
+    /// A symlink whose target doesn't exist, like after the service was removed
+    Dangling,
+    /// A symlink to a directory outside of svdir, which svctrl refuses to touch
+    Foreign,
+    /// A file or directory instead of a symlink
+    Plain,
+}
+
+/// `fmt::Display` for Problem, used by doctor
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        match self {
+            Problem::Dangling => write!(f, "dangling symlink"),
+            Problem::Foreign => write!(f, "foreign symlink"),
+            Problem::Plain => write!(f, "not a symlink"),
+        }
+    }
+}
+
+/// Entry of the active services directory with a Problem
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    /// Name of the entry in the active services directory
+    pub name: String,
+    /// Path of the entry
+    pub path: PathBuf,
+    /// What is wrong with the entry
+    pub problem: Problem,
+    /// Where the entry points to if it is a symlink
+    pub target: Option<PathBuf>,
+}
+
+/// Returns whether a path is a directory of svdir, comparing the canonical paths
+/// if they differ so a svdir that is itself a symlink works
+fn in_svdir(c: &Config, p: &Path) -> bool {
+    let parent = match p.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+
+    if parent == c.svdir {
+        return true;
+    }
+
+    match (fs::canonicalize(parent), fs::canonicalize(&c.svdir)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Returns the entries of the active services directory that have a Problem, sorted by
+/// name, entries starting with a dot are skipped like runsvdir does
+///
+/// # Arguments
+///
+/// * `c` - Config holding svdir and lndir
+pub fn check_lndir(c: &Config) -> Result<Vec<Entry>, Error> {
+    let entries = match fs::read_dir(&c.lndir) {
+        Ok(e) => e,
+        Err(e) => return Err(Error::Read(c.lndir.clone(), e)),
+    };
+
+    let mut found: Vec<Entry> = Vec::new();
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let target = match fs::read_link(&path) {
+            Ok(t) => c.lndir.join(t),
+            Err(_) => {
+                found.push(Entry {
+                    name,
+                    path,
+                    problem: Problem::Plain,
+                    target: None,
+                });
+                continue;
+            }
+        };
+
+        let problem = if !path.exists() {
+            Problem::Dangling
+        } else if !in_svdir(c, &target) {
+            Problem::Foreign
+        } else {
+            continue;
+        };
+
+        found.push(Entry {
+            name,
+            path,
+            problem,
+            target: Some(target),
+        });
+    }
+
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(found)
+}
+
+/// Removes an entry found by `check_lndir`, only symlinks are ever removed
+pub fn remove_entry(e: &Entry) -> Result<(), Error> {
+    if e.problem == Problem::Plain {
+        if e.path.is_dir() {
+            return Err(Error::IsDir(e.path.clone()));
+        }
+        return Err(Error::IsFile(e.path.clone()));
+    }
+
+    match fs::remove_file(&e.path) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::Remove(e.path.clone(), err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_check_lndir() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = Config {
+            svdir: tmpdir.path().join("sv"),
+            lndir: tmpdir.path().join("service"),
+            ..Default::default()
+        };
+        let other = tmpdir.path().join("other");
+
+        fs::create_dir_all(c.svdir.join("ok")).unwrap();
+        fs::create_dir_all(&c.lndir).unwrap();
+        fs::create_dir_all(other.join("foreign")).unwrap();
+
+        symlink(c.svdir.join("ok"), c.lndir.join("ok")).unwrap();
+        symlink("../sv/ok", c.lndir.join("relative")).unwrap();
+        symlink(c.svdir.join("gone"), c.lndir.join("gone")).unwrap();
+        symlink(other.join("foreign"), c.lndir.join("foreign")).unwrap();
+        fs::create_dir(c.lndir.join("plain")).unwrap();
+        fs::create_dir(c.lndir.join(".hidden")).unwrap();
+
+        let found = check_lndir(&c).unwrap();
+        let problems: Vec<(&str, Problem)> =
+            found.iter().map(|e| (e.name.as_str(), e.problem)).collect();
+
+        assert_eq!(
+            problems,
+            vec![
+                ("foreign", Problem::Foreign),
+                ("gone", Problem::Dangling),
+                ("plain", Problem::Plain),
+            ]
+        );
+
+        for e in &found {
+            match e.problem {
+                Problem::Plain => assert!(remove_entry(e).is_err()),
+                _ => remove_entry(e).unwrap(),
+            }
+        }
+
+        assert_eq!(check_lndir(&c).unwrap().len(), 1);
+        assert!(c.lndir.join("ok").exists());
+        assert!(other.join("foreign").is_dir());
+    }
+}
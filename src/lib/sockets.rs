@@ -0,0 +1,261 @@
+use crate::errors::Error;
+use serde::Serialize;
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+/// Files of /proc/net read by `read`, each named after the protocol of its sockets
+pub const PROTOCOLS: [&str; 5] = ["tcp", "tcp6", "udp", "udp6", "unix"];
+
+/// State of a TCP socket that listens, TCP_LISTEN in the kernel
+const TCP_LISTEN: &str = "0A";
+
+/// State of a UDP socket that isn't connected to a peer, TCP_CLOSE in the kernel
+const UDP_UNCONNECTED: &str = "07";
+
+/// Flag of a unix socket that accepts connections, __SO_ACCEPTCON in the kernel
+const UNIX_ACCEPTCON: u32 = 0x10000;
+
+/// Type of a unix datagram socket, SOCK_DGRAM
+const UNIX_DGRAM: &str = "0002";
+
+/// Socket that listens, as described by a file of /proc/net
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Socket {
+    /// Protocol of the socket, the name of the file it was found in
+    pub proto: String,
+    /// Local address of the socket, the path for unix sockets, '@' followed by the
+    /// name for abstract ones and empty for unnamed ones
+    pub address: String,
+    /// Local port of the socket, None for unix sockets
+    pub port: Option<u16>,
+    /// Inode of the socket, which the links in /proc/PID/fd point to
+    pub inode: u64,
+}
+
+/// Decodes an address of /proc/net/tcp or tcp6 without its port
+///
+/// # Remarks
+///
+/// The kernel prints the address as 32 bit words in the byte order of the host,
+/// so the bytes of each word are in network order once they are back in memory.
+fn decode_address(hex: &str) -> Option<String> {
+    let mut bytes: Vec<u8> = Vec::new();
+
+    for i in (0..hex.len()).step_by(8) {
+        let word = u32::from_str_radix(hex.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+
+    match bytes.len() {
+        4 => Some(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()),
+        16 => {
+            let mut b = [0u8; 16];
+            b.copy_from_slice(&bytes);
+            Some(Ipv6Addr::from(b).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Parses the contents of /proc/net/tcp, tcp6, udp or udp6 and returns the sockets
+/// that listen, which are the TCP ones in LISTEN and the UDP ones without a peer
+///
+/// # Arguments
+///
+/// * `proto` - String slice holding the name of the file
+/// * `content` - String slice holding the contents of the file
+pub fn parse_inet(proto: &str, content: &str) -> Vec<Socket> {
+    let listening = if proto.starts_with("tcp") {
+        TCP_LISTEN
+    } else {
+        UDP_UNCONNECTED
+    };
+
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|l| {
+            let fields: Vec<&str> = l.split_whitespace().collect();
+
+            if fields.get(3) != Some(&listening) {
+                return None;
+            }
+
+            let mut local = fields.get(1)?.splitn(2, ':');
+            let address = decode_address(local.next()?)?;
+            let port = u16::from_str_radix(local.next()?, 16).ok()?;
+
+            Some(Socket {
+                proto: proto.to_string(),
+                address,
+                port: Some(port),
+                inode: fields.get(9)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Parses the contents of /proc/net/unix and returns the sockets that listen, which
+/// are the ones that accept connections and the datagram ones bound to a name
+pub fn parse_unix(content: &str) -> Vec<Socket> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|l| {
+            let fields: Vec<&str> = l.split_whitespace().collect();
+
+            let flags = u32::from_str_radix(fields.get(3)?, 16).ok()?;
+            let address = fields.get(7).unwrap_or(&"").to_string();
+
+            let dgram = *fields.get(4)? == UNIX_DGRAM && !address.is_empty();
+
+            if flags & UNIX_ACCEPTCON == 0 && !dgram {
+                return None;
+            }
+
+            Some(Socket {
+                proto: "unix".to_string(),
+                address,
+                port: None,
+                inode: fields.get(6)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Returns the sockets that listen in the network namespace of svctrl
+///
+/// # Arguments
+///
+/// * `root` - Path where proc is mounted, see `procfs::PROC`
+///
+/// # Remarks
+///
+/// The files of protocols the kernel doesn't have, like tcp6 without IPv6, are
+/// skipped.
+pub fn read(root: &Path) -> Result<Vec<Socket>, Error> {
+    let mut found: Vec<Socket> = Vec::new();
+
+    for proto in PROTOCOLS.iter() {
+        let path = root.join("net").join(proto);
+
+        let content = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(Error::Read(path, e)),
+        };
+
+        if *proto == "unix" {
+            found.extend(parse_unix(&content));
+        } else {
+            found.extend(parse_inet(proto, &content));
+        }
+    }
+
+    Ok(found)
+}
+
+/// Returns the inodes of the sockets a process has open, from the links in
+/// /proc/PID/fd that look like 'socket:[INODE]'
+///
+/// # Arguments
+///
+/// * `root` - Path where proc is mounted, see `procfs::PROC`
+/// * `pid` - Pid of the process
+///
+/// # Remarks
+///
+/// Only root can read the fds of processes of other users, those of a process that
+/// can't be read are left out.
+pub fn inodes(root: &Path, pid: u32) -> Vec<u64> {
+    let entries = match fs::read_dir(root.join(pid.to_string()).join("fd")) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| fs::read_link(e.path()).ok())
+        .filter_map(|l| {
+            l.to_str()?
+                .strip_prefix("socket:[")?
+                .strip_suffix(']')?
+                .parse()
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+                       0: 00000000:0050 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 101 1 0 100 0 0 10 0\n\
+                       1: 0100007F:BC8F 0100007F:9510 01 00000000:00000000 00:00000000 00000000     0        0 102 2 0 20 4 0 18 -1\n";
+
+    const TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+                        0: 00000000000000000000000001000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 201 1 0 100 0 0 10 0\n";
+
+    const UNIX: &str = "Num       RefCount Protocol Flags    Type St Inode Path\n\
+                        0000000000000000: 00000002 00000000 00010000 0001 01 301 /run/foo.sock\n\
+                        0000000000000000: 00000003 00000000 00000000 0001 03 302\n\
+                        0000000000000000: 00000002 00000000 00000000 0002 01 303 @foo-log\n";
+
+    #[test]
+    fn test_parse_inet() {
+        assert_eq!(
+            parse_inet("tcp", TCP),
+            vec![Socket {
+                proto: "tcp".to_string(),
+                address: "0.0.0.0".to_string(),
+                port: Some(80),
+                inode: 101,
+            }]
+        );
+
+        let v6 = parse_inet("tcp6", TCP6);
+        assert_eq!(v6.len(), 1);
+        assert_eq!(v6[0].port, Some(8080));
+        if cfg!(target_endian = "little") {
+            assert_eq!(v6[0].address, "::1");
+        }
+
+        // Only UDP sockets without a peer listen
+        assert!(parse_inet("udp", TCP).is_empty());
+    }
+
+    #[test]
+    fn test_parse_unix() {
+        let found: Vec<(String, u64)> = parse_unix(UNIX)
+            .into_iter()
+            .map(|s| (s.address, s.inode))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("/run/foo.sock".to_string(), 301),
+                ("@foo-log".to_string(), 303)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inodes() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let fd = tmpdir.path().join("10").join("fd");
+        fs::create_dir_all(&fd).unwrap();
+
+        symlink("socket:[101]", fd.join("3")).unwrap();
+        symlink("/dev/null", fd.join("0")).unwrap();
+        symlink("pipe:[55]", fd.join("1")).unwrap();
+
+        assert_eq!(inodes(tmpdir.path(), 10), vec![101]);
+        assert!(inodes(tmpdir.path(), 11).is_empty());
+    }
+}
@@ -0,0 +1,348 @@
+use crate::errors::Error;
+use std::fmt::Result as fmtResult;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// TAI64 label of the UNIX epoch, 2^62 plus the TAI-UTC offset
+///
+/// # Remarks
+///
+/// runit, like the rest of djb's libtai, assumes the system clock counts TAI - 10
+/// seconds and ignores leap seconds, so every label it writes is the UNIX time plus
+/// the 10 seconds that TAI was ahead of UTC in 1972. Using the same fixed offset
+/// gives back exactly what the system clock said when runsv or svlogd wrote the label.
+pub const UNIX_EPOCH_LABEL: u64 = (1 << 62) + 10;
+
+/// Size in bytes of a packed TAI64N timestamp, 8 for the label and 4 for nanoseconds
+pub const PACKED_SIZE: usize = 12;
+
+/// Size in characters of an external TAI64N label like svlogd -t writes, '@'
+/// followed by 24 hex digits
+pub const LABEL_SIZE: usize = 25;
+
+/// A TAI64N timestamp as written by runsv in supervise/status and by svlogd -t
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tai64N {
+    /// TAI64 label, seconds since 2^62 seconds before the TAI epoch
+    pub secs: u64,
+    /// Nanoseconds after the second, always below 1000000000
+    pub nanos: u32,
+}
+
+/// `fmt::Display` for Tai64N, the external '@' form svlogd -t writes
+impl Display for Tai64N {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        write!(f, "@{:016x}{:08x}", self.secs, self.nanos)
+    }
+}
+
+/// Parses the external '@' form of a TAI64N label
+impl FromStr for Tai64N {
+    type Err = Error;
+
+    fn from_str(l: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::Tai64Label(l.to_string());
+
+        if l.len() != LABEL_SIZE || !l.starts_with('@') || !l.is_char_boundary(1) {
+            return Err(invalid());
+        }
+
+        // from_str_radix accepts a sign, a label doesn't
+        if !l[1..].bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let secs = u64::from_str_radix(&l[1..17], 16).map_err(|_| invalid())?;
+        let nanos = u32::from_str_radix(&l[17..25], 16).map_err(|_| invalid())?;
+
+        if nanos >= 1_000_000_000 {
+            return Err(invalid());
+        }
+
+        Ok(Self { secs, nanos })
+    }
+}
+
+/// Converts a time of the system clock to TAI64N, times too far before the UNIX
+/// epoch for a label become label 0
+impl From<SystemTime> for Tai64N {
+    fn from(t: SystemTime) -> Self {
+        match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => Self {
+                secs: UNIX_EPOCH_LABEL + d.as_secs(),
+                nanos: d.subsec_nanos(),
+            },
+            Err(e) => {
+                let d = e.duration();
+
+                // Nanoseconds count forward from the second before
+                let secs = UNIX_EPOCH_LABEL.checked_sub(d.as_secs());
+
+                match secs {
+                    Some(s) if d.subsec_nanos() == 0 => Self { secs: s, nanos: 0 },
+                    Some(s) if s > 0 => Self {
+                        secs: s - 1,
+                        nanos: 1_000_000_000 - d.subsec_nanos(),
+                    },
+                    _ => Self { secs: 0, nanos: 0 },
+                }
+            }
+        }
+    }
+}
+
+impl Tai64N {
+    /// Returns the time of the system clock the timestamp stands for, None if the
+    /// system clock can't hold it, like for labels far from 2^62
+    pub fn system_time(&self) -> Option<SystemTime> {
+        let nanos = Duration::from_nanos(u64::from(self.nanos));
+
+        if self.secs >= UNIX_EPOCH_LABEL {
+            UNIX_EPOCH
+                .checked_add(Duration::from_secs(self.secs - UNIX_EPOCH_LABEL))?
+                .checked_add(nanos)
+        } else {
+            UNIX_EPOCH
+                .checked_sub(Duration::from_secs(UNIX_EPOCH_LABEL - self.secs))?
+                .checked_add(nanos)
+        }
+    }
+
+    /// Returns the timestamp stored in its packed form, the label and the
+    /// nanoseconds both big endian
+    ///
+    /// # Arguments
+    ///
+    /// * `b` - The `PACKED_SIZE` bytes holding the timestamp
+    ///
+    /// # Remarks
+    ///
+    /// Nanoseconds above a second are carried into the label, runit never
+    /// writes them. Returns None if that overflows the label.
+    pub fn from_bytes(b: [u8; PACKED_SIZE]) -> Option<Self> {
+        let mut label: [u8; 8] = [0; 8];
+        let mut nano: [u8; 4] = [0; 4];
+
+        label.copy_from_slice(&b[0..8]);
+        nano.copy_from_slice(&b[8..12]);
+
+        let nanos = u32::from_be_bytes(nano);
+
+        Some(Self {
+            secs: u64::from_be_bytes(label).checked_add(u64::from(nanos / 1_000_000_000))?,
+            nanos: nanos % 1_000_000_000,
+        })
+    }
+
+    /// Returns the packed form of the timestamp, see `Tai64N::from_bytes`
+    pub fn to_bytes(&self) -> [u8; PACKED_SIZE] {
+        let mut b: [u8; PACKED_SIZE] = [0; PACKED_SIZE];
+
+        b[0..8].copy_from_slice(&self.secs.to_be_bytes());
+        b[8..12].copy_from_slice(&self.nanos.to_be_bytes());
+
+        b
+    }
+
+    /// Returns the timestamp of the current time of the system clock
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+}
+
+/// Formats a time of the system clock as UTC in the form 2019-04-01 12:34:56.123456789
+///
+/// # Remarks
+///
+/// Times before the UNIX epoch are formatted as the epoch.
+pub fn format_utc(t: SystemTime) -> String {
+    let d = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = d.as_secs();
+
+    // Days since the epoch to a civil date, from Howard Hinnant's date algorithms
+    let z = secs / 86400 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09}",
+        year,
+        month,
+        day,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        d.subsec_nanos()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let t: Tai64N = "@400000005c7ac8f41d1b6c4c".parse().unwrap();
+
+        assert_eq!(t.secs, 0x4000_0000_5c7a_c8f4);
+        assert_eq!(t.nanos, 0x1d1b_6c4c);
+        assert_eq!(t.to_string(), "@400000005c7ac8f41d1b6c4c");
+
+        // Upper case hex digits are read but written back in lower case
+        let t: Tai64N = "@400000005C7AC8F41D1B6C4C".parse().unwrap();
+        assert_eq!(t.to_string(), "@400000005c7ac8f41d1b6c4c");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for l in [
+            "",
+            "@",
+            "400000005c7ac8f41d1b6c4c",
+            "@400000005c7ac8f41d1b6c4",
+            "@400000005c7ac8f41d1b6c4c0",
+            "#400000005c7ac8f41d1b6c4c",
+            "@400000005c7ac8f41d1b6c4g",
+            "@+00000005c7ac8f41d1b6c4c",
+            "@400000005c7ac8f4+d1b6c4c",
+            "@400000005c7ac8f43b9aca00",
+            "@400000005c7ac8f41d1b6c\u{e9}",
+        ]
+        .iter()
+        {
+            match l.parse::<Tai64N>() {
+                Err(Error::Tai64Label(s)) => assert_eq!(&s, l),
+                _ => panic!("expected Error::Tai64Label for {}", l),
+            }
+        }
+    }
+
+    #[test]
+    fn test_system_time() {
+        let t: Tai64N = "@400000005c7ac8f41d1b6c4c".parse().unwrap();
+        let s = t.system_time().unwrap();
+
+        // 10 seconds of TAI-UTC offset
+        assert_eq!(s, UNIX_EPOCH + Duration::new(0x5c7a_c8f4 - 10, 0x1d1b_6c4c));
+        assert_eq!(Tai64N::from(s), t);
+
+        assert_eq!(
+            Tai64N::from(UNIX_EPOCH),
+            Tai64N {
+                secs: UNIX_EPOCH_LABEL,
+                nanos: 0
+            }
+        );
+        assert_eq!(
+            Tai64N::from(UNIX_EPOCH).to_string(),
+            "@400000000000000a00000000"
+        );
+    }
+
+    #[test]
+    fn test_system_time_before_epoch() {
+        let s = UNIX_EPOCH - Duration::new(1, 250_000_000);
+        let t = Tai64N::from(s);
+
+        assert_eq!(t.secs, UNIX_EPOCH_LABEL - 2);
+        assert_eq!(t.nanos, 750_000_000);
+        assert_eq!(t.system_time().unwrap(), s);
+
+        let s = UNIX_EPOCH - Duration::from_secs(86400);
+        assert_eq!(Tai64N::from(s).system_time().unwrap(), s);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let t: Tai64N = "@400000005c7ac8f41d1b6c4c".parse().unwrap();
+        let b = t.to_bytes();
+
+        assert_eq!(
+            b,
+            [0x40, 0, 0, 0, 0x5c, 0x7a, 0xc8, 0xf4, 0x1d, 0x1b, 0x6c, 0x4c]
+        );
+        assert_eq!(Tai64N::from_bytes(b), Some(t));
+
+        // Too many nanoseconds are carried into the label
+        let mut b = t.to_bytes();
+        b[8..12].copy_from_slice(&1_500_000_000u32.to_be_bytes());
+        let c = Tai64N::from_bytes(b).unwrap();
+        assert_eq!(c.secs, t.secs + 1);
+        assert_eq!(c.nanos, 500_000_000);
+
+        b[0..8].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(Tai64N::from_bytes(b), None);
+    }
+
+    #[test]
+    fn test_system_time_out_of_range() {
+        for l in ["@ffffffffffffffff00000000", "@ffffffffffffffff3b9ac9ff"].iter() {
+            let t: Tai64N = l.parse().unwrap();
+            assert_eq!(t.system_time(), None, "{}", l);
+        }
+
+        // Whether the system clock holds the lowest labels depends on the platform,
+        // they must only not panic
+        let t: Tai64N = "@000000000000000000000000".parse().unwrap();
+        assert_eq!(
+            t.system_time(),
+            UNIX_EPOCH.checked_sub(Duration::from_secs(UNIX_EPOCH_LABEL))
+        );
+
+        // Just below the label of the epoch
+        let t: Tai64N = "@400000000000000900000000".parse().unwrap();
+        assert_eq!(t.system_time(), Some(UNIX_EPOCH - Duration::from_secs(1)));
+
+        let far = UNIX_EPOCH - Duration::from_secs(1 << 62) - Duration::new(20, 1);
+        assert_eq!(Tai64N::from(far), Tai64N { secs: 0, nanos: 0 });
+    }
+
+    #[test]
+    fn test_order() {
+        let a: Tai64N = "@400000005c7ac8f41d1b6c4c".parse().unwrap();
+        let b: Tai64N = "@400000005c7ac8f41d1b6c4d".parse().unwrap();
+        let c: Tai64N = "@400000005c7ac8f500000000".parse().unwrap();
+
+        assert!(a < b && b < c);
+        assert!(a.to_string() < b.to_string() && b.to_string() < c.to_string());
+        assert!(Tai64N::now() > c);
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01 00:00:00.000000000");
+        assert_eq!(
+            format_utc(UNIX_EPOCH - Duration::from_secs(1)),
+            "1970-01-01 00:00:00.000000000"
+        );
+        assert_eq!(
+            format_utc(UNIX_EPOCH + Duration::new(951_782_400, 1)),
+            "2000-02-29 00:00:00.000000001"
+        );
+        assert_eq!(
+            format_utc(UNIX_EPOCH + Duration::from_secs(951_868_799)),
+            "2000-02-29 23:59:59.000000000"
+        );
+        assert_eq!(
+            format_utc(UNIX_EPOCH + Duration::from_secs(4_107_542_400)),
+            "2100-03-01 00:00:00.000000000"
+        );
+        assert_eq!(
+            format_utc(
+                "@400000005c7ac8f41d1b6c4c"
+                    .parse::<Tai64N>()
+                    .unwrap()
+                    .system_time()
+                    .unwrap()
+            ),
+            "2019-03-02 18:18:18.488336460"
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use crate::configuration::Config;
+use crate::envdir;
+use crate::errors::Error;
+use crate::user::quote;
+use crate::utils::read_file;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Built in template of run, runsv starts it in the service directory
+pub const RUN: &str = "#!/bin/sh\n\
+                       # Generated by svctrl new for @NAME@\n\
+                       exec 2>&1\n\
+                       exec @CHPST@@EXEC@\n";
+
+/// Built in template of finish, runsv runs it after run exits with the exit code
+/// of run, or -1 if it didn't exit normally, and the signal that killed it
+pub const FINISH: &str = "#!/bin/sh\n\
+                          # Generated by svctrl new for @NAME@, $1 is the exit code of run\n\
+                          # and $2 the signal that killed it\n\
+                          exit 0\n";
+
+/// Built in template of log/run, runsv starts it in the log directory
+pub const LOG_RUN: &str = "#!/bin/sh\n\
+                           # Generated by svctrl new for @NAME@\n\
+                           [ -d @LOGDIR@ ] || mkdir -p @LOGDIR@\n\
+                           exec svlogd -tt @LOGDIR@\n";
+
+/// Returns whether a string is a valid name for a service, which is a single path
+/// component that runsvdir doesn't ignore, without control characters that would
+/// end the comments of the scripts it is written in
+pub fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains('/')
+        && !name.starts_with('.')
+        && !name.chars().any(char::is_control)
+}
+
+/// Returns a template with each placeholder replaced by its value in a single pass,
+/// so placeholders inside the values are left as they are
+fn substitute(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(i) = rest.find('@') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        match values.iter().find(|(p, _)| rest.starts_with(p)) {
+            Some((p, v)) => {
+                out.push_str(v);
+                rest = &rest[p.len()..];
+            }
+            None => {
+                out.push('@');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Service directory to create with `NewService::create`
+#[derive(Debug, Default)]
+pub struct NewService {
+    /// Name of the service
+    pub name: String,
+    /// Command that run replaces itself with, used verbatim by sh
+    pub exec: String,
+    /// User that run runs the command as with chpst
+    pub user: Option<String>,
+    /// Whether to create log/run that logs the output of run with svlogd
+    pub log: bool,
+    /// Variables written to env/, which run sets with chpst -e
+    pub env: Vec<(String, String)>,
+}
+
+/// Writes an executable script
+fn write_script(path: &Path, content: &str) -> Result<(), Error> {
+    if let Err(e) = fs::write(path, content) {
+        return Err(Error::Write(path.to_path_buf(), e));
+    }
+
+    if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o755)) {
+        return Err(Error::Write(path.to_path_buf(), e));
+    }
+
+    Ok(())
+}
+
+impl NewService {
+    /// Returns the arguments given to chpst before the command, ending in a space,
+    /// env/ is relative to the service directory runsv starts run in
+    fn chpst(&self) -> String {
+        match &self.user {
+            Some(u) => format!("chpst -u {} -e ./env ", quote(u)),
+            None => String::from("chpst -e ./env "),
+        }
+    }
+
+    /// Returns the directory svlogd logs to, named after the service in logdir of the
+    /// configuration or else in /var/log
+    fn logdir(&self, c: &Config) -> PathBuf {
+        match &c.logdir {
+            Some(l) => l.join(&self.name),
+            None => PathBuf::from("/var/log").join(&self.name),
+        }
+    }
+
+    /// Returns a script of the service from its template in templatedir, or else from
+    /// the built in one, with the placeholders replaced
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - Config holding templatedir and logdir
+    /// * `file` - String slice holding the path of the script relative to the
+    ///   service directory
+    /// * `builtin` - String slice holding the template used if templatedir has none
+    ///
+    /// # Remarks
+    ///
+    /// The placeholders are @NAME@, @EXEC@, @CHPST@ and @LOGDIR@, which don't clash
+    /// with the syntax of sh.
+    pub fn render(&self, c: &Config, file: &str, builtin: &str) -> Result<String, Error> {
+        let custom = c.templatedir.join(file);
+
+        let template = if custom.is_file() {
+            read_file(&custom)?
+        } else {
+            builtin.to_string()
+        };
+
+        Ok(substitute(
+            &template,
+            &[
+                ("@NAME@", &self.name),
+                ("@EXEC@", &self.exec),
+                ("@CHPST@", &self.chpst()),
+                ("@LOGDIR@", &quote(&self.logdir(c).to_string_lossy())),
+            ],
+        ))
+    }
+
+    /// Creates the service directory in svdir and returns its path
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - Config holding svdir, templatedir and logdir
+    ///
+    /// # Remarks
+    ///
+    /// The directory is written as .NAME.svctrl, which runsvdir ignores, and renamed
+    /// to NAME once complete so a half written service is never seen. An existing
+    /// service directory is never overwritten.
+    pub fn create(&self, c: &Config) -> Result<PathBuf, Error> {
+        if !is_valid(&self.name) {
+            return Err(Error::InvalidService(self.name.clone()));
+        }
+
+        let dir = c.svdir.join(&self.name);
+
+        if dir.symlink_metadata().is_ok() {
+            return Err(Error::Exists(dir));
+        }
+
+        let tmp = c.svdir.join(format!(".{}.svctrl", self.name));
+
+        // Left behind by a previous run that failed
+        if tmp.exists() {
+            if let Err(e) = fs::remove_dir_all(&tmp) {
+                return Err(Error::Remove(tmp, e));
+            }
+        }
+
+        let result = self.write(c, &tmp).and_then(|_| {
+            if dir.symlink_metadata().is_ok() {
+                return Err(Error::Exists(dir.clone()));
+            }
+
+            match fs::rename(&tmp, &dir) {
+                Ok(_) => Ok(dir.clone()),
+                Err(e) => Err(Error::Rename(tmp.clone(), dir.clone(), e)),
+            }
+        });
+
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&tmp);
+        }
+
+        result
+    }
+
+    /// Writes the files of the service directory in a directory
+    fn write(&self, c: &Config, dir: &Path) -> Result<(), Error> {
+        let env = dir.join("env");
+
+        if let Err(e) = fs::create_dir_all(&env) {
+            return Err(Error::Write(env, e));
+        }
+
+        for (k, v) in &self.env {
+            envdir::set(&env, k, v)?;
+        }
+
+        write_script(&dir.join("run"), &self.render(c, "run", RUN)?)?;
+        write_script(&dir.join("finish"), &self.render(c, "finish", FINISH)?)?;
+
+        if self.log {
+            let log = dir.join("log");
+
+            if let Err(e) = fs::create_dir_all(&log) {
+                return Err(Error::Write(log, e));
+            }
+
+            write_script(&log.join("run"), &self.render(c, "log/run", LOG_RUN)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use crate::lint;
+
+    fn config(tmpdir: &tempfile::TempDir) -> Config {
+        Config {
+            svdir: tmpdir.path().join("sv"),
+            templatedir: tmpdir.path().join("templates"),
+            logdir: Some(PathBuf::from("/var/log/sv")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_create() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = config(&tmpdir);
+        fs::create_dir(&c.svdir).unwrap();
+
+        let new = NewService {
+            name: "foo".to_string(),
+            exec: "/usr/bin/foo -f".to_string(),
+            user: Some("nobody".to_string()),
+            log: true,
+            env: vec![("HOME".to_string(), "/var/empty".to_string())],
+        };
+
+        let dir = new.create(&c).unwrap();
+        assert_eq!(dir, c.svdir.join("foo"));
+
+        let run = fs::read_to_string(dir.join("run")).unwrap();
+        assert!(run.ends_with("exec chpst -u 'nobody' -e ./env /usr/bin/foo -f\n"));
+
+        let log = fs::read_to_string(dir.join("log/run")).unwrap();
+        assert!(log.ends_with("exec svlogd -tt '/var/log/sv/foo'\n"));
+
+        assert_eq!(
+            fs::read_to_string(dir.join("env/HOME")).unwrap(),
+            "/var/empty\n"
+        );
+        assert!(lint::check_dir(&dir).is_empty());
+        assert!(!c.svdir.join(".foo.svctrl").exists());
+
+        match new.create(&c) {
+            Err(Error::Exists(p)) => assert_eq!(p, dir),
+            _ => panic!("expected Error::Exists"),
+        }
+    }
+
+    #[test]
+    fn test_create_from_templatedir() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = config(&tmpdir);
+        fs::create_dir(&c.svdir).unwrap();
+        fs::create_dir(&c.templatedir).unwrap();
+        fs::write(
+            c.templatedir.join("run"),
+            "#!/bin/sh\n# @NAME@\nexec @EXEC@\n",
+        )
+        .unwrap();
+
+        let new = NewService {
+            name: "bar".to_string(),
+            exec: "bar".to_string(),
+            ..Default::default()
+        };
+
+        let dir = new.create(&c).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("run")).unwrap(),
+            "#!/bin/sh\n# bar\nexec bar\n"
+        );
+        // The templates that aren't in templatedir are the built in ones
+        assert!(fs::read_to_string(dir.join("finish"))
+            .unwrap()
+            .contains("for bar"));
+        assert!(!dir.join("log").exists());
+
+        for name in ["../baz", "baz\nrm -rf /"].iter() {
+            let new = NewService {
+                name: name.to_string(),
+                ..Default::default()
+            };
+
+            match new.create(&c) {
+                Err(Error::InvalidService(_)) => (),
+                _ => panic!("expected Error::InvalidService for {:?}", name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_substitute() {
+        let values = [("@NAME@", "foo"), ("@EXEC@", "echo @NAME@ a@b")];
+
+        assert_eq!(
+            substitute("# @NAME@\nexec @EXEC@ @@NAME@ @X@\n", &values),
+            "# foo\nexec echo @NAME@ a@b @foo @X@\n"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::configuration::Config;
+use crate::errors::Error;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use users::os::unix::UserExt;
+
+/// Prefix of the name of the service that runs the runsvdir of a user, the name of
+/// the user follows it
+pub const SERVICE_PREFIX: &str = "runsvdir-";
+
+/// Returns whether a string can be used as the name of a user in the name of a
+/// service and in the scripts written by `setup`
+pub fn is_valid(user: &str) -> bool {
+    !user.is_empty()
+        && !user.contains('/')
+        && !user.starts_with('.')
+        && !user.chars().any(char::is_control)
+}
+
+/// Returns the home directory of a user as the system's user database knows it,
+/// which includes users from NSS like LDAP
+///
+/// # Remarks
+///
+/// If the database doesn't know the user running svctrl, like in a container
+/// without an entry for its uid, $HOME is used for them.
+pub fn home_of(user: &str) -> Result<PathBuf, Error> {
+    if let Some(u) = users::get_user_by_name(user) {
+        return Ok(u.home_dir().to_path_buf());
+    }
+
+    let current = std::env::var_os("USER").or_else(|| std::env::var_os("LOGNAME"));
+
+    match (current, std::env::var_os("HOME")) {
+        (Some(c), Some(h)) if c == user => Ok(PathBuf::from(h)),
+        _ => Err(Error::NoUser(user.to_string())),
+    }
+}
+
+/// Quotes a string to be used as a single word by sh
+pub(crate) fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Returns the run script of the service that runs runsvdir as a user on the
+/// directory where the user enables their services
+///
+/// # Arguments
+///
+/// * `user` - String slice holding the name of the user
+/// * `home` - Path of the home directory of the user
+/// * `lndir` - Path of the directory where the user enables their services
+///
+/// # Remarks
+///
+/// The script creates lndir as the user if it doesn't exist yet, so that it isn't
+/// owned by root.
+pub fn run_script(user: &str, home: &Path, lndir: &Path) -> String {
+    format!(
+        "#!/bin/sh\n\
+         # Generated by svctrl user-setup, runs the services of {user}\n\
+         export USER={quser}\n\
+         export HOME={home}\n\
+         groups=\"$(id -Gn \"$USER\" | tr ' ' ':')\"\n\
+         svdir={lndir}\n\
+         [ -d \"$svdir\" ] || chpst -u \"$USER:$groups\" mkdir -p \"$svdir\"\n\
+         exec chpst -u \"$USER:$groups\" runsvdir \"$svdir\"\n",
+        user = user,
+        quser = quote(user),
+        home = quote(&home.to_string_lossy()),
+        lndir = quote(&lndir.to_string_lossy()),
+    )
+}
+
+/// Creates the service that runs runsvdir as a user in svdir of the system
+/// configuration and returns the path of its service directory
+///
+/// # Arguments
+///
+/// * `c` - Config of the system, holding svdir
+/// * `user` - String slice holding the name of the user
+/// * `home` - Path of the home directory of the user
+///
+/// # Remarks
+///
+/// The services of the user are taken from the default lndir of `Config::user_in`,
+/// as the configuration of another user can't be known. The service is not enabled
+/// and an existing service directory is never overwritten.
+///
+/// Like `template::NewService::create` the directory is written under a name
+/// runsvdir ignores and renamed once complete.
+pub fn setup(c: &Config, user: &str, home: &Path) -> Result<PathBuf, Error> {
+    if !is_valid(user) {
+        return Err(Error::InvalidUser(user.to_string()));
+    }
+
+    let name = format!("{}{}", SERVICE_PREFIX, user);
+    let dir = c.svdir.join(&name);
+
+    if dir.symlink_metadata().is_ok() {
+        return Err(Error::Exists(dir));
+    }
+
+    let tmp = c.svdir.join(format!(".{}.svctrl", name));
+
+    // Left behind by a previous run that failed
+    if tmp.exists() {
+        if let Err(e) = fs::remove_dir_all(&tmp) {
+            return Err(Error::Remove(tmp, e));
+        }
+    }
+
+    let result = write(&tmp, user, home).and_then(|_| {
+        if dir.symlink_metadata().is_ok() {
+            return Err(Error::Exists(dir.clone()));
+        }
+
+        match fs::rename(&tmp, &dir) {
+            Ok(_) => Ok(dir.clone()),
+            Err(e) => Err(Error::Rename(tmp.clone(), dir.clone(), e)),
+        }
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&tmp);
+    }
+
+    result
+}
+
+/// Writes the files of the service of `setup` in a directory
+fn write(dir: &Path, user: &str, home: &Path) -> Result<(), Error> {
+    if let Err(e) = fs::create_dir_all(dir) {
+        return Err(Error::Write(dir.to_path_buf(), e));
+    }
+
+    let run = dir.join("run");
+    let lndir = Config::user_in(home).lndir;
+
+    if let Err(e) = fs::write(&run, run_script(user, home, &lndir)) {
+        return Err(Error::Write(run, e));
+    }
+
+    if let Err(e) = fs::set_permissions(&run, fs::Permissions::from_mode(0o755)) {
+        return Err(Error::Write(run, e));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    #[test]
+    fn test_home_of() {
+        // root is in the user database of every system
+        assert!(home_of("root").is_ok());
+
+        match home_of("no such user") {
+            Err(Error::NoUser(u)) => assert_eq!(u, "no such user"),
+            _ => panic!("expected Error::NoUser"),
+        }
+    }
+
+    #[test]
+    fn test_setup() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let c = Config {
+            svdir: tmpdir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let dir = setup(&c, "alice", Path::new("/home/alice")).unwrap();
+        assert_eq!(dir, tmpdir.path().join("runsvdir-alice"));
+
+        let run = fs::read_to_string(dir.join("run")).unwrap();
+        assert!(run.contains("export HOME='/home/alice'\n"));
+        assert!(run.contains("svdir='/home/alice/.local/service'\n"));
+        assert!(run.ends_with("exec chpst -u \"$USER:$groups\" runsvdir \"$svdir\"\n"));
+
+        let mode = fs::metadata(dir.join("run")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+
+        match setup(&c, "alice", Path::new("/home/alice")) {
+            Err(Error::Exists(p)) => assert_eq!(p, dir),
+            _ => panic!("expected Error::Exists"),
+        }
+
+        for user in ["", "../alice", "alice\nrm -rf ~", ".alice"].iter() {
+            match setup(&c, user, Path::new("/home/alice")) {
+                Err(Error::InvalidUser(_)) => (),
+                _ => panic!("expected Error::InvalidUser for {:?}", user),
+            }
+        }
+        assert_eq!(fs::read_dir(tmpdir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+}
@@ -1,7 +1,9 @@
 use crate::errors::Error;
-use std::fs::read_to_string;
+use std::fs::{metadata, read, read_to_string, remove_file, rename, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 /// Writes to a fifo and returns and raises an error if not possible
 ///
@@ -53,3 +55,150 @@ pub fn read_file(p: &PathBuf) -> Result<String, Error> {
         Err(e) => Err(Error::Read(p.clone(), e)),
     }
 }
+
+/// Reads a file to a vector of bytes and returns it or raises an error, used for
+/// binary files like supervise/status
+///
+/// # Arguments
+///
+/// * `p` - `PathBuf` to file that should be read
+///
+/// # Example
+///
+/// ```ignore
+/// let file = PathBuf::new("/var/service/sshd/supervise/status");
+///
+/// let bytes = read_bytes(&file)?;
+/// ```
+pub fn read_bytes(p: &PathBuf) -> Result<Vec<u8>, Error> {
+    match read(p) {
+        Ok(b) => Ok(b),
+        Err(e) => Err(Error::Read(p.clone(), e)),
+    }
+}
+
+/// Writes a file through a temporary file that is renamed over it, so nothing
+/// reading it meanwhile, like runsv or a collector, sees half of it
+///
+/// # Arguments
+///
+/// * `p` - Path of the file
+/// * `content` - Bytes that should be written to the file
+///
+/// # Remarks
+///
+/// The temporary file is .NAME.svctrl in the same directory, which runsv, chpst and
+/// runsvdir skip as it starts with a dot. It gets the mode of the file before the
+/// content is written, so a file only its owner can read, like one holding a
+/// secret, stays that way.
+///
+/// # Example
+///
+/// ```ignore
+/// let file = PathBuf::from("/etc/sv/sshd/env/HOME");
+///
+/// write_atomic(&file, b"/root")?;
+/// ```
+pub fn write_atomic(p: &Path, content: &[u8]) -> Result<(), Error> {
+    let tmp = match p.file_name() {
+        Some(f) => p.with_file_name(format!(".{}.svctrl", f.to_string_lossy())),
+        None => {
+            let e = std::io::Error::from(std::io::ErrorKind::InvalidInput);
+            return Err(Error::Write(p.to_path_buf(), e));
+        }
+    };
+
+    let perms = metadata(p).ok().map(|m| m.permissions());
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    if let Some(perms) = &perms {
+        options.mode(perms.mode());
+    }
+
+    // The mode given to open is masked by the umask, so it is set again
+    let written = options.open(&tmp).and_then(|mut f| {
+        if let Some(perms) = perms {
+            f.set_permissions(perms)?;
+        }
+        f.write_all(content)
+    });
+
+    if let Err(e) = written {
+        let _ = remove_file(&tmp);
+        return Err(Error::Write(tmp, e));
+    }
+
+    if let Err(e) = rename(&tmp, p) {
+        let _ = remove_file(&tmp);
+        return Err(Error::Rename(tmp, p.to_path_buf(), e));
+    }
+
+    Ok(())
+}
+
+/// Returns when a time from now is over, None if it is too far ahead for an Instant
+/// to hold, which is then a deadline that is never reached
+///
+/// # Arguments
+///
+/// * `t` - Time from now
+pub fn deadline(t: Duration) -> Option<Instant> {
+    Instant::now().checked_add(t)
+}
+
+/// Returns the time left until a deadline, 0 if it passed, see `deadline`
+pub fn time_left(deadline: Option<Instant>) -> Duration {
+    match deadline {
+        Some(d) => d.saturating_duration_since(Instant::now()),
+        None => Duration::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    #[test]
+    fn test_write_atomic() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let p = tmpdir.path().join("foo");
+
+        write_atomic(&p, b"a").unwrap();
+        write_atomic(&p, b"b").unwrap();
+
+        assert_eq!(read_to_string(&p).unwrap(), "b");
+        assert_eq!(tmpdir.path().read_dir().unwrap().count(), 1);
+
+        // The mode of the file is kept
+        std::fs::set_permissions(&p, std::fs::Permissions::from_mode(0o600)).unwrap();
+        write_atomic(&p, b"secret").unwrap();
+
+        assert_eq!(metadata(&p).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(read_to_string(&p).unwrap(), "secret");
+
+        // The temporary file is removed when it can't be renamed
+        std::fs::create_dir(tmpdir.path().join("bar")).unwrap();
+        std::fs::write(tmpdir.path().join("bar/x"), "").unwrap();
+
+        match write_atomic(&tmpdir.path().join("bar"), b"a") {
+            Err(Error::Rename(..)) => (),
+            _ => panic!("expected Error::Rename"),
+        }
+        assert!(!tmpdir.path().join(".bar.svctrl").exists());
+    }
+
+    #[test]
+    fn test_deadline() {
+        assert_eq!(deadline(Duration::MAX), None);
+        assert_eq!(time_left(None), Duration::MAX);
+
+        let d = deadline(Duration::from_secs(60));
+        assert!(time_left(d) <= Duration::from_secs(60));
+        assert!(time_left(d) > Duration::from_secs(50));
+        assert_eq!(time_left(deadline(Duration::ZERO)), Duration::ZERO);
+    }
+}
@@ -0,0 +1,326 @@
+use crate::service::{State, Status};
+use inotify::{Inotify, WatchMask};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Result as fmtResult;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time between each look at whether runsv wrote a status or the watch was stopped
+pub const TICK: Duration = Duration::from_millis(100);
+
+/// Change between two statuses of a service
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Change {
+    /// The service went from one state to another
+    State {
+        /// State before the change
+        from: State,
+        /// State after the change
+        to: State,
+    },
+    /// The main process of the service changed, 0 is no process
+    Pid {
+        /// Pid before the change
+        from: u32,
+        /// Pid after the change
+        to: u32,
+    },
+    /// runsv started the service again without being asked to, as it is wanted up
+    Restart,
+}
+
+/// `fmt::Display` for Change, like 'run -> down' or 'pid 12 -> 34'
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter) -> fmtResult {
+        match self {
+            Change::State { from, to } => write!(f, "{} -> {}", from, to),
+            Change::Pid { from, to } => write!(f, "pid {} -> {}", from, to),
+            Change::Restart => write!(f, "restarted"),
+        }
+    }
+}
+
+/// Returns whether a status shows that runsv restarted a service since an earlier
+/// one, which is a new main process while runsv wanted the service up before, so
+/// bringing up a service that was down on purpose isn't one
+///
+/// # Arguments
+///
+/// * `pid` - Pid of the main process in the earlier status
+/// * `want_up` - Whether runsv wanted the service up in the earlier status
+/// * `new` - Later status
+pub fn is_restart(pid: u32, want_up: bool, new: &Status) -> bool {
+    new.state() == State::Run && new.pid() != 0 && new.pid() != pid && want_up
+}
+
+/// Returns the changes from one status of a service to a later one
+///
+/// # Remarks
+///
+/// Restarts faster than the statuses are read only show up as a new pid, see
+/// `is_restart`.
+pub fn changes(old: &Status, new: &Status) -> Vec<Change> {
+    let mut found: Vec<Change> = Vec::new();
+
+    if old.state() != new.state() {
+        found.push(Change::State {
+            from: old.state(),
+            to: new.state(),
+        });
+    }
+
+    if old.pid() != new.pid() {
+        found.push(Change::Pid {
+            from: old.pid(),
+            to: new.pid(),
+        });
+
+        if is_restart(old.pid(), old.want_up(), new) {
+            found.push(Change::Restart);
+        }
+    }
+
+    found
+}
+
+/// Keeps the last status of each service that is watched and when it restarted
+pub struct Tracker {
+    /// Time in which restarts are counted
+    window: Duration,
+    /// Last status of each service
+    last: HashMap<String, Status>,
+    /// Times at which each service restarted within the window, oldest first
+    restarts: HashMap<String, VecDeque<SystemTime>>,
+}
+
+impl Tracker {
+    /// Returns a Tracker that counts the restarts in a window of time
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last: HashMap::new(),
+            restarts: HashMap::new(),
+        }
+    }
+
+    /// Records a new status of a service and returns how it changed, nothing the first
+    /// time a service is seen
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - String slice holding the name the service is tracked by
+    /// * `s` - Status just read
+    /// * `now` - Time the status was read at
+    pub fn update(&mut self, name: &str, s: Status, now: SystemTime) -> Vec<Change> {
+        let found = match self.last.get(name) {
+            Some(old) => changes(old, &s),
+            None => Vec::new(),
+        };
+
+        if found.contains(&Change::Restart) {
+            self.restarts
+                .entry(name.to_string())
+                .or_default()
+                .push_back(now);
+        }
+
+        self.last.insert(name.to_string(), s);
+
+        found
+    }
+
+    /// Returns how many times a service restarted within the window before a time
+    pub fn restarts(&mut self, name: &str, now: SystemTime) -> usize {
+        let window = self.window;
+
+        match self.restarts.get_mut(name) {
+            Some(times) => {
+                while let Some(t) = times.front() {
+                    match now.duration_since(*t) {
+                        Ok(d) if d > window => times.pop_front(),
+                        _ => break,
+                    };
+                }
+                times.len()
+            }
+            None => 0,
+        }
+    }
+
+    /// Returns the last status of a service
+    pub fn last(&self, name: &str) -> Option<&Status> {
+        self.last.get(name)
+    }
+}
+
+/// Waits for runsv to write supervise/status of services with inotify, or only for
+/// time to pass if inotify can't be used
+pub struct Watcher {
+    /// None if inotify isn't available or was disabled
+    inotify: Option<Inotify>,
+    /// Buffer the events are read into
+    buffer: [u8; 4096],
+}
+
+impl Watcher {
+    /// Returns a Watcher of the supervise directories given, those that can't be
+    /// watched are skipped and without inotify `Watcher::wait` always times out
+    ///
+    /// # Arguments
+    ///
+    /// * `dirs` - Paths of supervise directories
+    /// * `inotify` - Whether to use inotify
+    ///
+    /// # Remarks
+    ///
+    /// runsv writes status.new and renames it over status, so the directory is
+    /// watched instead of the file, which is replaced each time.
+    pub fn new<P: AsRef<Path>>(dirs: &[P], inotify: bool) -> Self {
+        let inotify = if inotify { Inotify::init().ok() } else { None };
+
+        let inotify = inotify.map(|mut i| {
+            for d in dirs {
+                let _ = i.add_watch(d.as_ref(), WatchMask::MOVED_TO | WatchMask::CLOSE_WRITE);
+            }
+            i
+        });
+
+        Self {
+            inotify,
+            buffer: [0; 4096],
+        }
+    }
+
+    /// Returns whether inotify is used
+    pub fn uses_inotify(&self) -> bool {
+        self.inotify.is_some()
+    }
+
+    /// Waits until a status is written, the time is over or stop is set, returns
+    /// whether a status was written
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - Maximum amount of time to wait
+    /// * `stop` - Flag set by another thread, like a handler of SIGINT
+    pub fn wait(&mut self, t: Duration, stop: &AtomicBool) -> bool {
+        let deadline = Instant::now() + t;
+
+        while !stop.load(Ordering::SeqCst) && Instant::now() < deadline {
+            if let Some(i) = &mut self.inotify {
+                if let Ok(events) = i.read_events(&mut self.buffer) {
+                    let written = events.filter_map(|e| e.name).any(|n| n == "status");
+
+                    if written {
+                        return true;
+                    }
+                }
+            }
+
+            std::thread::sleep(TICK.min(deadline.saturating_duration_since(Instant::now())));
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_changes() {
+        let up = Status::fake(State::Run, 10, SystemTime::now(), true, false);
+
+        assert!(changes(
+            &up,
+            &Status::fake(State::Run, 10, SystemTime::now(), true, false)
+        )
+        .is_empty());
+        assert_eq!(
+            changes(
+                &up,
+                &Status::fake(State::Run, 11, SystemTime::now(), true, false)
+            ),
+            vec![Change::Pid { from: 10, to: 11 }, Change::Restart]
+        );
+        assert_eq!(
+            changes(
+                &up,
+                &Status::fake(State::Down, 0, SystemTime::now(), false, false)
+            ),
+            vec![
+                Change::State {
+                    from: State::Run,
+                    to: State::Down
+                },
+                Change::Pid { from: 10, to: 0 },
+            ]
+        );
+        // Brought up on purpose
+        assert!(!changes(
+            &Status::fake(State::Down, 0, SystemTime::now(), false, false),
+            &up
+        )
+        .contains(&Change::Restart));
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut t = Tracker::new(Duration::from_secs(60));
+        let start = SystemTime::now();
+
+        assert!(t
+            .update(
+                "foo",
+                Status::fake(State::Run, 10, SystemTime::now(), true, false),
+                start
+            )
+            .is_empty());
+
+        for pid in 11..14 {
+            let now = start + Duration::from_secs(u64::from(pid - 10));
+            assert!(t
+                .update(
+                    "foo",
+                    Status::fake(State::Run, pid, SystemTime::now(), true, false),
+                    now
+                )
+                .contains(&Change::Restart));
+        }
+
+        assert_eq!(t.restarts("foo", start + Duration::from_secs(5)), 3);
+        assert_eq!(t.restarts("foo", start + Duration::from_secs(62)), 2);
+        assert_eq!(t.restarts("bar", start), 0);
+        assert_eq!(t.last("foo").unwrap().pid(), 13);
+    }
+
+    #[test]
+    fn test_watcher() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let stop = AtomicBool::new(false);
+        let mut w = Watcher::new(&[tmpdir.path()], true);
+
+        fs::write(tmpdir.path().join("status.new"), b"").unwrap();
+        fs::rename(
+            tmpdir.path().join("status.new"),
+            tmpdir.path().join("status"),
+        )
+        .unwrap();
+
+        assert!(w.wait(Duration::from_secs(1), &stop));
+        assert!(!w.wait(Duration::from_millis(10), &stop));
+
+        stop.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+        assert!(!Watcher::new(&[tmpdir.path()], false).wait(Duration::from_secs(5), &stop));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}